@@ -0,0 +1,254 @@
+use crate::error::{Result, WhichCmdError};
+
+use serde::Deserialize;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The fields accepted on a choice given as a mapping
+pub const CHOICE_FIELDS: &[&str] = &["name", "value"];
+
+/// An option offered by a choice node. `name` is shown and matched in the
+/// fuzzy select, `value` is inserted into the command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub name: String,
+    pub value: String,
+}
+
+impl From<&str> for Choice {
+    fn from(value: &str) -> Self {
+        Choice {
+            name: value.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+// A choice is either a plain string used as both name and value, or a mapping
+// with a separate name
+impl<'de> Deserialize<'de> for Choice {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Choice, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged, deny_unknown_fields)]
+        enum ChoiceHelper {
+            Plain(String),
+            Labeled { name: Option<String>, value: String },
+        }
+
+        match ChoiceHelper::deserialize(deserializer)? {
+            ChoiceHelper::Plain(value) => Ok(Choice::from(value.as_str())),
+            ChoiceHelper::Labeled { name, value } => Ok(Choice {
+                name: name.unwrap_or_else(|| value.clone()),
+                value,
+            }),
+        }
+    }
+}
+
+/// Runs `command` with `sh -c` and returns each non-empty line of its output
+/// as a choice. The command is killed, along with any process it started, if
+/// it does not finish within `timeout`, or if its output is still held open by
+/// a process left behind by then.
+pub fn run_choices_command(command: &str, timeout: Duration) -> Result<Vec<Choice>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // A group of its own, so that the processes the shell starts can be
+        // killed with it
+        .process_group(0)
+        .spawn()
+        .map_err(|e| {
+            WhichCmdError::ChoicesCommand(format!("failed to run `{}`: {}", command, e))
+        })?;
+
+    // Read the pipes on separate threads so that a chatty command can't block
+    // on a full pipe while we wait for it to exit
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let timed_out = |child: &mut Child| {
+        kill_group(child);
+        WhichCmdError::ChoicesCommand(format!(
+            "`{}` timed out after {}ms",
+            command,
+            timeout.as_millis()
+        ))
+    };
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            return Err(timed_out(&mut child));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let Ok(stdout) = stdout.recv_timeout(remaining) else {
+        return Err(timed_out(&mut child));
+    };
+    if !status.success() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let stderr = stderr.recv_timeout(remaining).unwrap_or_default();
+        let reason = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim().to_string())
+            .unwrap_or_else(|| status.to_string());
+        return Err(WhichCmdError::ChoicesCommand(format!(
+            "`{}` failed: {}",
+            command, reason
+        )));
+    }
+
+    let choices: Vec<Choice> = stdout
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(Choice::from)
+        .collect();
+
+    if choices.is_empty() {
+        return Err(WhichCmdError::ChoicesCommand(format!(
+            "`{}` produced no choices",
+            command
+        )));
+    }
+
+    Ok(choices)
+}
+
+/// Kills the process group led by `child`, and reaps `child` if it is still
+/// running
+fn kill_group(child: &mut Child) {
+    if let Ok(pgid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` has no memory safety requirements, and the group is
+        // the one `child` was started in
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+    }
+    let _ = child.wait();
+}
+
+/// Reads `pipe` to the end on a separate thread, sending what was read once
+/// the pipe is closed
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        let _ = sender.send(output);
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_run_choices_command_splits_lines() {
+        let choices = run_choices_command("printf 'main\\nfeature/x\\n\\n'", TIMEOUT).unwrap();
+        assert_eq!(
+            choices,
+            vec![Choice::from("main"), Choice::from("feature/x")]
+        );
+    }
+
+    #[test]
+    fn test_run_choices_command_failure_reports_stderr() {
+        let error = run_choices_command("echo 'not a git repository' >&2; exit 128", TIMEOUT)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not a git repository"));
+    }
+
+    #[test]
+    fn test_run_choices_command_empty_output() {
+        let error = run_choices_command("true", TIMEOUT)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("no choices"));
+    }
+
+    #[test]
+    fn test_run_choices_command_timeout() {
+        let error = run_choices_command("sleep 5", Duration::from_millis(50))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("timed out"));
+    }
+
+    #[test]
+    fn test_run_choices_command_timeout_kills_started_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let command = format!("sleep 1 && touch '{}'", marker.display());
+        let error = run_choices_command(&command, Duration::from_millis(50))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("timed out"));
+
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists(), "the command outlived the timeout");
+    }
+
+    #[test]
+    fn test_run_choices_command_output_held_by_background_process() {
+        let start = Instant::now();
+        let error = run_choices_command("echo main; sleep 5 &", Duration::from_millis(200))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_choice_deserialize_plain() {
+        let choice: Choice = serde_yaml::from_str("prod").unwrap();
+        assert_eq!(choice, Choice::from("prod"));
+    }
+
+    #[test]
+    fn test_choice_deserialize_labeled() {
+        let yaml = r#"{name: "Production (eu-west)", value: "--context prod-euw"}"#;
+        let choice: Choice = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(choice.name, "Production (eu-west)");
+        assert_eq!(choice.value, "--context prod-euw");
+    }
+
+    #[test]
+    fn test_choice_deserialize_value_only() {
+        let choice: Choice = serde_yaml::from_str("value: staging").unwrap();
+        assert_eq!(choice.name, "staging");
+        assert_eq!(choice.value, "staging");
+    }
+
+    #[test]
+    fn test_choice_accepts_every_listed_field() {
+        let yaml: String = CHOICE_FIELDS
+            .iter()
+            .map(|field| format!("{}: {}\n", field, field))
+            .collect();
+        let choice: Choice = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(choice.name, "name");
+        assert_eq!(choice.value, "value");
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{COMMAND_HISTORY_FILE_NAME, MAX_COMMAND_HISTORY_ENTRIES};
+use crate::error::Result;
+use crate::node_id::NodeId;
+use crate::store::YamlStore;
+use crate::usage::now;
+
+/// A command built in the TUI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandEntry {
+    pub command: String,
+    /// The nodes the command was built from
+    pub path: Vec<NodeId>,
+    /// When the command was built, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The working directory the command was built in
+    pub cwd: Option<PathBuf>,
+    /// Whether the command was run right away rather than inserted
+    pub immediate: bool,
+}
+
+impl CommandEntry {
+    /// An entry for a command built now, in the current working directory
+    pub fn new(command: String, path: Vec<NodeId>, immediate: bool) -> Self {
+        CommandEntry {
+            command,
+            path,
+            timestamp: now(),
+            cwd: std::env::current_dir().ok(),
+            immediate,
+        }
+    }
+}
+
+/// The commands built before, most recent first.
+///
+/// The history is stored as YAML in the XDG data directory. A command built
+/// again replaces its earlier entry, so that every command appears once.
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    store: YamlStore<Vec<CommandEntry>>,
+}
+
+impl CommandHistory {
+    /// Opens the history in the XDG data directory
+    pub fn open() -> Self {
+        CommandHistory {
+            store: YamlStore::open(COMMAND_HISTORY_FILE_NAME),
+        }
+    }
+
+    /// The commands built before, most recent first
+    pub fn entries(&self) -> &[CommandEntry] {
+        &self.store.data
+    }
+
+    /// Records `entry` as the most recent command
+    pub fn record(&mut self, entry: CommandEntry) {
+        if entry.command.is_empty() {
+            return;
+        }
+        let entries = &mut self.store.data;
+        entries.retain(|e| e.command != entry.command);
+        entries.insert(0, entry);
+        entries.truncate(MAX_COMMAND_HISTORY_ENTRIES);
+    }
+
+    /// Writes the history to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        self.store.save()
+    }
+}
+
+/// How long ago something happened, e.g. "3h ago", given its age in seconds
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        86400..604800 => format!("{}d ago", seconds / 86400),
+        _ => format!("{}w ago", seconds / 604800),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(path: PathBuf) -> CommandHistory {
+        CommandHistory {
+            store: YamlStore::load(path),
+        }
+    }
+
+    fn entry(command: &str) -> CommandEntry {
+        CommandEntry {
+            command: command.to_string(),
+            path: vec!["k g p".parse().unwrap()],
+            timestamp: 1_767_225_600,
+            cwd: Some(PathBuf::from("/srv")),
+            immediate: false,
+        }
+    }
+
+    fn commands(history: &CommandHistory) -> Vec<&str> {
+        history
+            .entries()
+            .iter()
+            .map(|e| e.command.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_record_puts_most_recent_first() {
+        let mut history = CommandHistory::default();
+        history.record(entry("kubectl get pods"));
+        history.record(entry("git status"));
+        history.record(entry("kubectl get pods"));
+        history.record(entry(""));
+        assert_eq!(commands(&history), ["kubectl get pods", "git status"]);
+    }
+
+    #[test]
+    fn test_record_keeps_a_limited_number_of_entries() {
+        let mut history = CommandHistory::default();
+        for i in 0..MAX_COMMAND_HISTORY_ENTRIES + 2 {
+            history.record(entry(&format!("echo {}", i)));
+        }
+        assert_eq!(history.entries().len(), MAX_COMMAND_HISTORY_ENTRIES);
+        assert_eq!(commands(&history).last(), Some(&"echo 2"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COMMAND_HISTORY_FILE_NAME);
+
+        let mut history = load(path.clone());
+        history.record(entry("kubectl get pods -n 'my ns'"));
+        history.save().unwrap();
+
+        let loaded = load(path);
+        assert_eq!(loaded.entries(), history.entries());
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(150), "2m ago");
+        assert_eq!(format_age(3 * 3600), "3h ago");
+        assert_eq!(format_age(86400), "1d ago");
+        assert_eq!(format_age(30 * 86400), "4w ago");
+    }
+}
@@ -1,25 +1,99 @@
 use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
 
 use crate::config::Config;
 use crate::error::Result;
 use crate::options::Options;
-use crate::tui;
+use crate::quote::single_quote;
+use crate::tui::{self, BuiltCommand};
 
 use crate::constants::*;
 
-pub fn build_command(immediate: bool, border: bool, height: usize) -> Result<()> {
+pub fn build_command(
+    immediate: bool,
+    border: bool,
+    height: usize,
+    record_history: bool,
+    config_path: Option<&Path>,
+) -> Result<()> {
     let opts = Options {
         print_immediate_tag: immediate,
         border,
         height,
+        record_history,
     };
 
-    let config = Config::from_file()?;
-    let command = tui::run_tui(config, opts)?;
+    let config = Config::from_file(config_path)?;
+    let built = tui::run_tui(config, opts)?;
+    write_output(&built)
+}
 
+/// Writes the command and its secrets for `which-cmd get` to pass on
+pub(super) fn write_output(built: &BuiltCommand) -> Result<()> {
     let xdg_dirs = xdg::BaseDirectories::with_prefix(PREFIX)?;
     let output_path = xdg_dirs.place_data_file(OUTPUT_FILE_NAME)?;
-    fs::write(output_path, command)?;
+    fs::write(output_path, &built.command)?;
+
+    let env_path = xdg_dirs.place_data_file(ENV_FILE_NAME)?;
+    write_env_file(&env_path, &built.env)?;
+
+    Ok(())
+}
+
+/// Writes `export` lines for the secrets of the command, readable only by the
+/// user, for `which-cmd get --env` to pass on. The names are also added to the
+/// `which_cmd_secrets` array, so that the shell integrations can unset them
+/// once the command has run. Leftovers of an earlier command are removed when
+/// there are none.
+fn write_env_file(path: &Path, env: &[(String, String)]) -> Result<()> {
+    // Removing the file first ensures that it is created with the permissions below
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    if env.is_empty() {
+        return Ok(());
+    }
 
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    for (name, value) in env {
+        writeln!(file, "export {}={}", name, single_quote(value))?;
+    }
+    let names: Vec<&str> = env.iter().map(|(name, _)| name.as_str()).collect();
+    writeln!(file, "which_cmd_secrets+=({})", names.join(" "))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_write_env_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ENV_FILE_NAME);
+
+        let env = vec![
+            ("API_TOKEN".to_string(), "it's secret".to_string()),
+            ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+        ];
+        write_env_file(&path, &env).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "export API_TOKEN='it'\\''s secret'\n\
+             export DB_PASSWORD='hunter2'\n\
+             which_cmd_secrets+=(API_TOKEN DB_PASSWORD)\n"
+        );
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        write_env_file(&path, &[]).unwrap();
+        assert!(!path.exists());
+    }
+}
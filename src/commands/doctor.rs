@@ -1,7 +1,11 @@
-use crate::{config::Config, search::get_search_options};
+use std::path::Path;
 
-pub fn doctor_command() {
-    let config = match Config::from_file() {
+use std::rc::Rc;
+
+use crate::{config::Config, node::Node, node_id::NodeId, settings::Bindings};
+
+pub fn doctor_command(config_path: Option<&Path>) {
+    let config = match Config::from_file(config_path) {
         Ok(cfg) => cfg,
         Err(e) => {
             eprintln!("Error loading configuration: {}", e);
@@ -9,11 +13,107 @@ pub fn doctor_command() {
         }
     };
 
-    let search_options = get_search_options(&config.keys);
-
-    if search_options.iter().any(|n| n.id.contains('/')) {
-        eprintln!("Warning: found node bound to the '/' character, search will be unavailable.");
+    println!("Loaded configuration layers:");
+    for layer in &config.layers {
+        println!("  {}", layer.path.display());
+        if !layer.overridden.is_empty() {
+            println!("    overrides: {}", join_ids(&layer.overridden));
+        }
+        if !layer.hidden.is_empty() {
+            println!("    hides: {}", join_ids(&layer.hidden));
+        }
+        for key_path in &layer.unmatched {
+            eprintln!(
+                "Warning: '{}' in {} does not match any node.",
+                key_path,
+                layer.path.display()
+            );
+        }
     }
 
+    warn_about_shadowed_bindings(&config.keys, &config.settings.bindings);
+
     println!("Configuration file is valid.");
 }
+
+fn join_ids(ids: &[NodeId]) -> String {
+    ids.iter()
+        .map(NodeId::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Warns about nodes bound to a key that is also bound to an action. Nodes
+/// take precedence, so the action is unavailable while the node is shown.
+fn warn_about_shadowed_bindings(nodes: &[Rc<Node>], bindings: &Bindings) {
+    for node in nodes {
+        for action in bindings.actions_for(&node.key) {
+            eprintln!(
+                "Warning: node '{}' is bound to {}, {} will be unavailable alongside it.",
+                node.id, node.key, action
+            );
+        }
+        warn_about_shadowed_bindings(&node.keys, bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::error::WhichCmdError;
+
+    #[test]
+    fn test_doctor_reports_every_problem_at_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("commands.yml");
+        let yaml = r#"keys:
+  - key: g
+    value: git
+    fleting: true
+    choices_cmd: ""
+    keys:
+      - key: s
+        value: status
+  - key: b
+    value: branch
+    multi: true
+    match: exact
+  - key: l
+    value: log
+    timeout: 1000
+    separator: ","
+  - key: c
+    value: commit -m {input}
+    default: wip
+"#;
+        std::fs::write(&path, yaml).unwrap();
+
+        let error = Config::from_file(Some(&path)).unwrap_err();
+        let WhichCmdError::InvalidConfig(diagnostics) = error else {
+            panic!("Expected InvalidConfig, got {:?}", error);
+        };
+        let found: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.span.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (4, "unknown field `fleting`"),
+                (5, "node has both `keys` and `choices_cmd`"),
+                (
+                    11,
+                    "`multi` only applies to nodes with `choices` or `choices_cmd`"
+                ),
+                (
+                    12,
+                    "`match` only applies to nodes with `choices` or `choices_cmd`"
+                ),
+                (15, "`timeout` only applies to nodes with `choices_cmd`"),
+                (16, "`separator` only applies to nodes with `multi: true`"),
+                (19, "`default` only applies to nodes with `input`"),
+                (18, "`{input}` is never filled on this node"),
+            ]
+        );
+    }
+}
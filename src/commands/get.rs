@@ -3,8 +3,21 @@ use std::fs;
 use crate::constants::*;
 use crate::error::Result;
 
-pub fn get_command() -> Result<()> {
+/// Prints the last built command, or with `env` the `export` lines for its
+/// secrets, and removes it so that it is only used once
+pub fn get_command(env: bool) -> Result<()> {
     let xdg_dirs = xdg::BaseDirectories::with_prefix(PREFIX)?;
+    if env {
+        let env_path = xdg_dirs.place_data_file(ENV_FILE_NAME)?;
+        // Most commands have no secrets, in which case there is no file
+        if env_path.exists() {
+            let contents = fs::read_to_string(&env_path)?;
+            fs::remove_file(&env_path)?;
+            print!("{}", contents);
+        }
+        return Ok(());
+    }
+
     let output_path = xdg_dirs.place_data_file(OUTPUT_FILE_NAME)?;
     let contents = fs::read_to_string(&output_path)?;
     fs::remove_file(&output_path)?;
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use crate::command_history::{format_age, CommandHistory};
+use crate::config::Config;
+use crate::error::Result;
+use crate::options::Options;
+use crate::tui;
+use crate::usage::now;
+
+use super::build::write_output;
+
+/// Lists the commands built before, oldest first, or with `pick` lets the
+/// user pick one for `which-cmd get` to print
+pub fn history_command(
+    pick: bool,
+    immediate: bool,
+    border: bool,
+    height: usize,
+    record_history: bool,
+    config_path: Option<&Path>,
+) -> Result<()> {
+    if pick {
+        let opts = Options {
+            print_immediate_tag: immediate,
+            border,
+            height,
+            record_history,
+        };
+        let config = Config::from_file(config_path)?;
+        let built = tui::run_history_picker(config, opts)?;
+        return write_output(&built);
+    }
+
+    let history = CommandHistory::open();
+    let now = now();
+    let longest_command = history
+        .entries()
+        .iter()
+        .map(|entry| entry.command.len())
+        .max()
+        .unwrap_or(0);
+    for entry in history.entries().iter().rev() {
+        let cwd = entry
+            .cwd
+            .as_ref()
+            .map(|cwd| cwd.display().to_string())
+            .unwrap_or_default();
+        let line = format!(
+            "{:>8}  {:<length$}  {}",
+            format_age(now.saturating_sub(entry.timestamp)),
+            entry.command,
+            cwd,
+            length = longest_command
+        );
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
@@ -9,12 +9,33 @@ pub enum Shell {
     BashTmux,
 }
 
+// The integrations strip the immediate prefix with parameter expansion rather
+// than `echo | cut`, so that values escaped by `crate::quote` reach the command
+// line unchanged (zsh's `echo` interprets backslash escapes). Secrets of
+// `Secret` inputs with an `env` are exported into the shell rather than
+// inserted, so that they don't end up in the shell history, and are unset again
+// before the next prompt so that they only reach the command they were entered
+// for.
 pub fn integration_command(shell: Shell) -> Result<()> {
     match shell {
         Shell::Zsh => {
             println!(
                 r#"
 # which-cmd integration for zsh
+
+# Exports the secrets of the command for the next command line only
+_which_cmd_export_secrets() {{
+    local env
+    env=$(which-cmd get --env)
+    [[ -z $env ]] && return
+    eval "$env"
+    (( ${{precmd_functions[(I)_which_cmd_unset_secrets]}} )) || precmd_functions+=(_which_cmd_unset_secrets)
+}}
+_which_cmd_unset_secrets() {{
+    unset "${{which_cmd_secrets[@]}}" which_cmd_secrets
+    precmd_functions=(${{precmd_functions:#_which_cmd_unset_secrets}})
+}}
+
 which_cmd_widget() {{
     local result
     # The <$TTY part ensures that which-cmd reads input from the terminal ($TTY) rather than from
@@ -23,11 +44,10 @@ which_cmd_widget() {{
     <$TTY which-cmd build --border --immediate
     if [[ $? -eq 0 ]]; then
         result=$(which-cmd get)
+        _which_cmd_export_secrets
         if [[ $result != "" ]]; then
           if [[ $result = __IMMEDIATE__* ]]; then
-            local cmd
-            cmd=$(echo $result | cut -d' ' -f2-)
-            LBUFFER+="$cmd"
+            LBUFFER+="${{result#__IMMEDIATE__ }}"
             zle accept-line
           else
             LBUFFER+="$result"
@@ -45,16 +65,29 @@ bindkey '^P' which_cmd_widget
             println!(
                 r#"
 # which-cmd integration for zsh + tmux
+
+# Exports the secrets of the command for the next command line only
+_which_cmd_export_secrets() {{
+  local env
+  env=$(which-cmd get --env)
+  [[ -z $env ]] && return
+  eval "$env"
+  (( ${{precmd_functions[(I)_which_cmd_unset_secrets]}} )) || precmd_functions+=(_which_cmd_unset_secrets)
+}}
+_which_cmd_unset_secrets() {{
+  unset "${{which_cmd_secrets[@]}}" which_cmd_secrets
+  precmd_functions=(${{precmd_functions:#_which_cmd_unset_secrets}})
+}}
+
 which_cmd_tmux_widget() {{
   if [[ $LBUFFER == "" ]]; then
     local result height=10
     tmux display-popup -S fg=brightblack -T '#[fg=white bold] which-cmd #[fg=default]' -y P -w 95% -h $((height + 2)) -b rounded -E "which-cmd build --height ${{height}} --border --immediate"
     result=$(which-cmd get)
+    _which_cmd_export_secrets
     if [[ $result != "" ]]; then
       if [[ $result = __IMMEDIATE__* ]]; then
-        local cmd
-        cmd=$(echo $result | cut -d' ' -f2-)
-        LBUFFER+="$cmd"
+        LBUFFER+="${{result#__IMMEDIATE__ }}"
         zle accept-line
       else
         LBUFFER+="$result"
@@ -75,19 +108,33 @@ bindkey ' ' which_cmd_tmux_widget
             println!(
                 r#"
 # which-cmd integration for bash + tmux
+
+# Exports the secrets of the command for the next command line only
+_which_cmd_export_secrets() {{
+  local env
+  env=$(which-cmd get --env)
+  [[ -z "$env" ]] && return
+  eval "$env"
+  [[ "$PROMPT_COMMAND" == _which_cmd_unset_secrets\;* ]] || PROMPT_COMMAND="_which_cmd_unset_secrets;$PROMPT_COMMAND"
+}}
+_which_cmd_unset_secrets() {{
+  unset "${{which_cmd_secrets[@]}}" which_cmd_secrets
+  PROMPT_COMMAND=${{PROMPT_COMMAND#_which_cmd_unset_secrets;}}
+}}
+
 which_cmd_tmux_widget() {{
   if [[ "$READLINE_LINE" == "" ]]; then
     local result height=10
     tmux display-popup -S fg=brightblack -T '#[fg=white bold] which-cmd #[fg=default]' -y P -w 95% -h $((height + 2)) -b rounded -E "which-cmd build --height ${{height}} --border --immediate"
     result=$(which-cmd get)
+    _which_cmd_export_secrets
     if [[ "$result" != "" ]]; then
       if [[ "$result" = __IMMEDIATE__* ]]; then
-        local cmd
-        cmd=$(echo "$result" | cut -d' ' -f2-)
-        READLINE_LINE="$cmd"
+        READLINE_LINE="${{result#__IMMEDIATE__ }}"
         READLINE_POINT=${{#READLINE_LINE}}
         # Simulate pressing Enter by inserting newline
         eval "$READLINE_LINE"
+        _which_cmd_unset_secrets
         READLINE_LINE=""
         READLINE_POINT=0
       else
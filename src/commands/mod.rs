@@ -1,9 +1,11 @@
 mod build;
 mod doctor;
 mod get;
+mod history;
 pub mod integration;
 
 pub use build::build_command;
 pub use doctor::doctor_command;
 pub use get::get_command;
+pub use history::history_command;
 pub use integration::integration_command;
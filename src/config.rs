@@ -1,90 +1,454 @@
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use crate::constants::*;
+use crate::diagnostic::{format_location, from_yaml_error, Diagnostic};
 use crate::error::{Result, WhichCmdError};
+use crate::key::Key;
 use crate::node::Node;
+use crate::node_id::NodeId;
+use crate::settings::{Settings, SettingsHelper};
+use crate::validate::{locate_root_key, validate};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config {
     pub keys: Vec<Rc<Node>>,
+    /// The configuration files applied on top of each other, in load order
+    pub layers: Vec<Layer>,
+    pub settings: Settings,
 }
 
+/// Describes what a single configuration layer changed in the tree
+#[derive(Debug, Default)]
+pub struct Layer {
+    pub path: PathBuf,
+    /// Ids of existing nodes that were replaced by this layer
+    pub overridden: Vec<NodeId>,
+    /// Ids of nodes that were removed by this layer
+    pub hidden: Vec<NodeId>,
+    /// Key paths referenced by `extend` or `hide` that matched no node
+    pub unmatched: Vec<String>,
+}
+
+/// The fields accepted at the root of a configuration file
+pub const CONFIG_FIELDS: &[&str] = &["include", "keys", "extend", "hide", "settings"];
+
+/// The fields accepted on an `extend` entry
+pub const EXTENSION_FIELDS: &[&str] = &["path", "keys"];
+
 // Helper struct for deserialization
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct ConfigHelper {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
     keys: Vec<Node>,
+    #[serde(default)]
+    extend: Vec<Extension>,
+    #[serde(default)]
+    hide: Vec<String>,
+    settings: Option<SettingsHelper>,
+}
+
+/// Nodes to add below the node at a key path, replacing children with the
+/// same key
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Extension {
+    path: String,
+    keys: Vec<Node>,
+}
+
+/// The nodes contributed by a single configuration file
+struct Source {
+    path: PathBuf,
+    contents: String,
+    keys: Vec<Node>,
+    extend: Vec<Extension>,
+    hide: Vec<String>,
+    settings: Option<SettingsHelper>,
 }
 
 impl Config {
-    pub fn from_file() -> Result<Self> {
+    /// Loads the configuration file at `config_path` on its own, so that the
+    /// result doesn't depend on the working directory. Without a path, the
+    /// global configuration is read from the XDG config directory, and any
+    /// project-local configuration files found above the working directory
+    /// are layered on top of it.
+    pub fn from_file(config_path: Option<&Path>) -> Result<Self> {
+        Config::from_file_in(config_path, &std::env::current_dir()?)
+    }
+
+    fn from_file_in(config_path: Option<&Path>, working_dir: &Path) -> Result<Self> {
+        let explicit = config_path.is_some();
+        let config_path = match config_path {
+            Some(path) => {
+                if !path.is_file() {
+                    return Err(WhichCmdError::ConfigNotFound {
+                        path: path.display().to_string(),
+                    });
+                }
+                path.to_path_buf()
+            }
+            None => Config::default_path()?,
+        };
+
+        // Check every layer even if one is invalid, so that all problems are
+        // reported at once
+        let mut diagnostics = Vec::new();
+        let contents = fs::read_to_string(&config_path)?;
+        let mut config = match Config::from_contents(&contents, &config_path) {
+            Err(WhichCmdError::InvalidConfig(found)) => {
+                diagnostics.extend(found);
+                Config::default()
+            }
+            result => result?,
+        };
+
+        if !explicit {
+            for layer_path in Config::find_project_layers(working_dir) {
+                let contents = fs::read_to_string(&layer_path)?;
+                match config.apply_layer(&contents, &layer_path) {
+                    Err(WhichCmdError::InvalidConfig(found)) => diagnostics.extend(found),
+                    result => result?,
+                }
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(WhichCmdError::InvalidConfig(diagnostics));
+        }
+
+        Ok(config)
+    }
+
+    fn default_path() -> Result<PathBuf> {
         let xdg_dirs = xdg::BaseDirectories::with_prefix(PREFIX)?;
-        let config_path = xdg_dirs.find_config_file(CONFIG_FILE_NAME).ok_or_else(|| {
-            WhichCmdError::ConfigNotFound {
+        xdg_dirs
+            .find_config_file(CONFIG_FILE_NAME)
+            .ok_or_else(|| WhichCmdError::ConfigNotFound {
                 path: format!(
                     "{}/{}",
                     xdg_dirs.get_config_home().display(),
                     CONFIG_FILE_NAME
                 ),
+            })
+    }
+
+    /// Parses the configuration file at `path` from `contents`. Relative
+    /// `include` patterns are resolved against the directory of `path`.
+    pub fn from_contents(contents: &str, path: &Path) -> Result<Self> {
+        let mut config = Config::default();
+        config.apply_layer(contents, path)?;
+        Ok(config)
+    }
+
+    /// Returns the project-local configuration files in `start` and its
+    /// ancestors, outermost first so that deeper files take precedence.
+    fn find_project_layers(start: &Path) -> Vec<PathBuf> {
+        let mut layers: Vec<PathBuf> = start
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+            .filter(|path| path.is_file())
+            .collect();
+        layers.reverse();
+        layers
+    }
+
+    /// Applies the configuration file at `path` on top of the current tree.
+    ///
+    /// Root keys are added, replacing existing root nodes with the same key.
+    /// `extend` entries do the same for the children of the node at a key
+    /// path, and `hide` removes the nodes at the given key paths. Keys must
+    /// still be unique within the file and the files it includes. Settings
+    /// override those of earlier layers, and those of a file override the
+    /// files it includes.
+    fn apply_layer(&mut self, contents: &str, path: &Path) -> Result<()> {
+        let mut diagnostics = Vec::new();
+        let mut sources =
+            Config::load_sources(contents, path, &mut HashSet::new(), &mut diagnostics)?;
+        Config::ensure_unique(&sources, &mut diagnostics)?;
+        if !diagnostics.is_empty() {
+            return Err(WhichCmdError::InvalidConfig(diagnostics));
+        }
+
+        let mut layer = Layer {
+            path: path.to_path_buf(),
+            ..Layer::default()
+        };
+
+        for source in sources.iter_mut().rev() {
+            if let Some(settings) = source.settings.take() {
+                self.settings.apply(settings);
+            }
+        }
+
+        let mut extensions = Vec::new();
+        let mut hidden_paths = Vec::new();
+        for source in sources {
+            for mut node in source.keys {
+                Config::set_id(&mut node, &NodeId::default());
+                Config::insert_node(&mut self.keys, node, &mut layer);
+            }
+            extensions.extend(source.extend.into_iter().map(|e| (e, source.path.clone())));
+            hidden_paths.extend(source.hide);
+        }
+
+        for (extension, source_path) in extensions {
+            let target = Config::parse_key_path(&extension.path)
+                .and_then(|key_path| Config::find_node_mut(&mut self.keys, &key_path));
+            let Some(target) = target else {
+                layer.unmatched.push(extension.path);
+                continue;
+            };
+            if target.has_choices() || target.input_type.is_some() {
+                return Err(WhichCmdError::InvalidLayer {
+                    path: source_path.display().to_string(),
+                    message: format!(
+                        "cannot extend `{}`, it already takes choices or input",
+                        extension.path
+                    ),
+                });
+            }
+
+            for mut node in extension.keys {
+                Config::set_id(&mut node, &target.id);
+                Config::insert_node(&mut target.keys, node, &mut layer);
+            }
+        }
+
+        for hidden_path in hidden_paths {
+            let Some(key_path) = Config::parse_key_path(&hidden_path) else {
+                layer.unmatched.push(hidden_path);
+                continue;
+            };
+            let Some((key, parent_path)) = key_path.split_last() else {
+                continue;
+            };
+            let siblings = if parent_path.is_empty() {
+                Some(&mut self.keys)
+            } else {
+                Config::find_node_mut(&mut self.keys, parent_path).map(|n| &mut n.keys)
+            };
+            match siblings.and_then(|s| s.iter().position(|n| n.key == *key).map(|i| (s, i))) {
+                Some((siblings, index)) => {
+                    let removed = siblings.remove(index);
+                    layer.hidden.push(removed.id.clone());
+                }
+                None => layer.unmatched.push(hidden_path),
             }
-        })?;
+        }
+
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// Adds `node` to `siblings`, replacing any existing node with the same key
+    fn insert_node(siblings: &mut Vec<Rc<Node>>, node: Node, layer: &mut Layer) {
+        if let Some(existing) = siblings.iter_mut().find(|n| n.key == node.key) {
+            layer.overridden.push(node.id.clone());
+            *existing = Rc::new(node);
+        } else {
+            siblings.push(Rc::new(node));
+        }
+    }
+
+    /// Parses a key path such as `g <C-r>`, with keys separated by whitespace
+    fn parse_key_path(path: &str) -> Option<Vec<Key>> {
+        path.split_whitespace()
+            .map(|key| key.parse().ok())
+            .collect()
+    }
 
-        let contents = fs::read_to_string(config_path)?;
+    /// Finds the node at the given (non-empty) key path
+    fn find_node_mut<'a>(keys: &'a mut [Rc<Node>], key_path: &[Key]) -> Option<&'a mut Node> {
+        let (first, rest) = key_path.split_first()?;
+        let node = keys.iter_mut().find(|n| n.key == *first)?;
+        let node = Rc::get_mut(node).expect("Should have exclusive access during initialization");
+        if rest.is_empty() {
+            Some(node)
+        } else {
+            Config::find_node_mut(&mut node.keys, rest)
+        }
+    }
 
-        Config::from_contents(&contents)
+    // Recursively loop through the config and set the id of each node.
+    // It should be the keys of all the parent nodes followed by the key of
+    // the current node.
+    fn set_id(node: &mut Node, parent_id: &NodeId) {
+        node.set_id_from_parent(parent_id);
+        for child in node.keys.iter_mut() {
+            // Get mutable reference to the node inside Rc
+            let child_mut =
+                Rc::get_mut(child).expect("Should have exclusive access during initialization");
+            Config::set_id(child_mut, &node.id);
+        }
     }
 
-    pub fn from_contents(contents: &str) -> Result<Self> {
-        let helper: ConfigHelper = serde_yaml::from_str(contents)?;
+    /// Parses `contents` as the file at `path` and recursively loads the files
+    /// matched by its `include` patterns, which are resolved relative to the
+    /// directory of the including file. Files that have already been loaded
+    /// are skipped, so include cycles terminate. The problems found in each
+    /// file are added to `diagnostics`, and its includes are still followed so
+    /// that they are checked too.
+    fn load_sources(
+        contents: &str,
+        path: &Path,
+        loaded: &mut HashSet<PathBuf>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<Source>> {
+        if let Ok(canonical) = path.canonicalize() {
+            loaded.insert(canonical);
+        }
 
-        // Recursively loop through the config and set the id of each node.
-        // It should be a concatenation of the keys of all the parent nodes
-        // and the key of the current node.
-        fn set_id(node: &mut Node, parent_id: &str) -> Result<()> {
-            node.set_id_from_parent(parent_id);
-            let keys: Vec<&str> = node.keys.iter().map(|n| n.key.as_str()).collect();
-            Config::ensure_unique(&node.id, &keys)?;
-            for child in node.keys.iter_mut() {
-                // Get mutable reference to the node inside Rc
-                let child_mut =
-                    Rc::get_mut(child).expect("Should have exclusive access during initialization");
-                set_id(child_mut, &node.id)?;
+        let found = validate(contents);
+        let helper = if found.is_empty() {
+            match serde_yaml::from_str::<ConfigHelper>(contents) {
+                Ok(helper) => Some(helper),
+                Err(e) => {
+                    let diagnostic = from_yaml_error(&e).ok_or(e)?;
+                    diagnostics.push(diagnostic.in_file(path, contents));
+                    None
+                }
+            }
+        } else {
+            diagnostics.extend(found.into_iter().map(|d| d.in_file(path, contents)));
+            None
+        };
+
+        let mut sources = Vec::new();
+        let includes = match helper {
+            Some(helper) => {
+                sources.push(Source {
+                    path: path.to_path_buf(),
+                    contents: contents.to_string(),
+                    keys: helper.keys,
+                    extend: helper.extend,
+                    hide: helper.hide,
+                    settings: helper.settings,
+                });
+                helper.include
+            }
+            None => Config::read_includes(contents),
+        };
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        for pattern in &includes {
+            for include_path in Config::resolve_include(base_dir, pattern)? {
+                if loaded.contains(&include_path.canonicalize()?) {
+                    continue;
+                }
+                let contents = fs::read_to_string(&include_path)?;
+                sources.extend(Config::load_sources(
+                    &contents,
+                    &include_path,
+                    loaded,
+                    diagnostics,
+                )?);
             }
-            Ok(())
         }
 
-        let keys: Vec<&str> = helper.keys.iter().map(|n| n.key.as_str()).collect();
-        Config::ensure_unique("", &keys)?;
+        Ok(sources)
+    }
+
+    /// Reads the `include` patterns of a file that could not be deserialized,
+    /// skipping any that are not strings
+    fn read_includes(contents: &str) -> Vec<String> {
+        let Ok(root) = serde_yaml::from_str::<serde_yaml::Value>(contents) else {
+            return Vec::new();
+        };
+        root.get("include")
+            .and_then(serde_yaml::Value::as_sequence)
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .filter_map(|p| p.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        let mut nodes = helper.keys;
-        for node in nodes.iter_mut() {
-            set_id(node, "")?;
+    /// Expands an include pattern into the list of files it refers to. A
+    /// pattern without glob metacharacters must name an existing file, while
+    /// a glob that matches nothing is not an error.
+    fn resolve_include(base_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+        if !pattern.contains(['*', '?', '[']) {
+            let include_path = base_dir.join(pattern);
+            if !include_path.is_file() {
+                return Err(WhichCmdError::ConfigNotFound {
+                    path: include_path.display().to_string(),
+                });
+            }
+            return Ok(vec![include_path]);
         }
 
-        Ok(Config {
-            keys: nodes.into_iter().map(Rc::new).collect(),
-        })
+        let base = glob::Pattern::escape(&base_dir.to_string_lossy());
+        let full_pattern = if Path::new(pattern).is_absolute() || base.is_empty() {
+            pattern.to_string()
+        } else {
+            format!("{}/{}", base, pattern)
+        };
+
+        let mut paths = Vec::new();
+        for entry in glob::glob(&full_pattern)? {
+            let path = entry.map_err(std::io::Error::from)?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
     }
 
-    fn ensure_unique(parent_id: &str, keys: &[&str]) -> Result<()> {
-        let mut seen = std::collections::HashSet::new();
-        for &key in keys {
-            if seen.contains(key) {
-                return Err(WhichCmdError::ConflictingKeys(format!(
-                    "{}{}",
-                    parent_id, key
-                )));
+    /// Ensures that no two root nodes of a layer share a key. Duplicates within
+    /// a single file are reported by validation, so this catches clashes
+    /// between a file and the files it includes. Clashes that can be located
+    /// are added to `diagnostics`.
+    fn ensure_unique(sources: &[Source], diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+        let mut seen: HashMap<Key, &Source> = HashMap::new();
+        for source in sources {
+            for node in &source.keys {
+                if let Some(first) = seen.get(&node.key) {
+                    diagnostics.push(Config::conflict_diagnostic(node.key, first, source)?);
+                    continue;
+                }
+                seen.insert(node.key, source);
             }
-            seen.insert(key);
         }
         Ok(())
     }
+
+    /// Describes a root key defined in two files, pointing at both definitions
+    fn conflict_diagnostic(key: Key, first: &Source, second: &Source) -> Result<Diagnostic> {
+        let first_span = locate_root_key(&first.contents, &key);
+        let second_span = locate_root_key(&second.contents, &key);
+        match (first_span, second_span) {
+            (Some(first_span), Some(second_span)) => Ok(Diagnostic::new(
+                format!("duplicate key `{}`", key),
+                second_span,
+            )
+            .in_file(&second.path, &second.contents)
+            .with_note(format!(
+                "`{}` is first defined at {}",
+                key,
+                format_location(&first.path, first_span)
+            ))),
+            _ => Err(WhichCmdError::ConflictingKeys(format!(
+                "{} in {} and {}",
+                key,
+                first.path.display(),
+                second.path.display()
+            ))),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::validate::struct_fields;
 
     #[test]
     fn test_config_parsing_basic() {
@@ -98,22 +462,53 @@ keys:
         name: status
         value: status
 "#;
-        let config = Config::from_contents(yaml).unwrap();
+        let config = Config::from_contents(yaml, Path::new("")).unwrap();
         assert_eq!(config.keys.len(), 1);
         let git_node = &config.keys[0];
-        assert_eq!(git_node.id, "g");
-        assert_eq!(git_node.key, "g");
+        assert_eq!(git_node.id.to_string(), "g");
+        assert_eq!(git_node.key, Key::char('g'));
         assert_eq!(git_node.name, "git");
         assert_eq!(git_node.value, "git");
         assert_eq!(git_node.keys.len(), 1);
         assert!(!git_node.is_loop);
         let status_node = &git_node.keys[0];
-        assert_eq!(status_node.id, "gs");
-        assert_eq!(status_node.key, "s");
+        assert_eq!(status_node.id.to_string(), "g s");
+        assert_eq!(status_node.key, Key::char('s'));
         assert_eq!(status_node.name, "status");
         assert_eq!(status_node.value, "status");
     }
 
+    #[test]
+    fn test_config_parsing_special_keys() {
+        let yaml = r#"
+keys:
+  - key: <C-r>
+    value: reload
+    keys:
+      - key: <Tab>
+        value: all
+      - key: "<lt>"
+        value: back
+"#;
+        let config = Config::from_contents(yaml, Path::new("")).unwrap();
+        let reload = &config.keys[0];
+        assert_eq!(reload.key, "<C-r>".parse().unwrap());
+        assert_eq!(reload.keys[0].id.to_string(), "<C-r> <Tab>");
+        assert_eq!(reload.keys[1].id.to_string(), "<C-r> <lt>");
+    }
+
+    #[test]
+    fn test_config_rejects_invalid_key() {
+        let yaml = "keys:\n  - key: <Ctrl-r>\n    value: reload\n";
+        match Config::from_contents(yaml, Path::new("")).unwrap_err() {
+            WhichCmdError::InvalidConfig(diagnostics) => {
+                assert_eq!(diagnostics[0].message, "invalid key `<Ctrl-r>`");
+                assert_eq!(diagnostics[0].location(), "2:10");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_config_parsing_no_name() {
         let yaml = r#"
@@ -121,10 +516,10 @@ keys:
   - key: g
     value: git
 "#;
-        let config = Config::from_contents(yaml).unwrap();
+        let config = Config::from_contents(yaml, Path::new("")).unwrap();
         assert_eq!(config.keys.len(), 1);
         let git_node = &config.keys[0];
-        assert_eq!(git_node.key, "g");
+        assert_eq!(git_node.key, Key::char('g'));
         assert_eq!(git_node.name, "git");
         assert_eq!(git_node.value, "git");
     }
@@ -136,10 +531,10 @@ keys:
   - key: g
     name: git commands
 "#;
-        let config = Config::from_contents(yaml).unwrap();
+        let config = Config::from_contents(yaml, Path::new("")).unwrap();
         assert_eq!(config.keys.len(), 1);
         let git_node = &config.keys[0];
-        assert_eq!(git_node.key, "g");
+        assert_eq!(git_node.key, Key::char('g'));
         assert_eq!(git_node.name, "git commands");
         assert_eq!(git_node.value, "");
     }
@@ -152,7 +547,7 @@ keys:
   - key: g
     keys: []
 "#;
-        let _ = Config::from_contents(yaml).unwrap();
+        let _ = Config::from_contents(yaml, Path::new("")).unwrap();
     }
 
     #[test]
@@ -163,10 +558,10 @@ keys:
     value: git
     loop: true
 "#;
-        let config = Config::from_contents(yaml).unwrap();
+        let config = Config::from_contents(yaml, Path::new("")).unwrap();
         assert_eq!(config.keys.len(), 1);
         let git_node = &config.keys[0];
-        assert_eq!(git_node.key, "g");
+        assert_eq!(git_node.key, Key::char('g'));
         assert!(git_node.is_loop);
     }
 
@@ -183,7 +578,7 @@ keys:
       - key: s
         value: stash
 "#;
-        let _ = Config::from_contents(yaml).unwrap();
+        let _ = Config::from_contents(yaml, Path::new("")).unwrap();
     }
 
     #[test]
@@ -200,6 +595,415 @@ keys:
       - key: s
         value: status
 "#;
-        let _ = Config::from_contents(yaml).unwrap();
+        let _ = Config::from_contents(yaml, Path::new("")).unwrap();
+    }
+
+    #[test]
+    fn test_config_include_merges_root_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(
+            dir.path().join("shared/git.yml"),
+            "keys:\n  - key: g\n    value: git\n    keys:\n      - key: s\n        value: status\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("shared/docker.yml"),
+            "keys:\n  - key: d\n    value: docker\n",
+        )
+        .unwrap();
+
+        let yaml = r#"
+include:
+  - shared/*.yml
+keys:
+  - key: l
+    value: ls
+"#;
+        let config = Config::from_contents(yaml, &dir.path().join(CONFIG_FILE_NAME)).unwrap();
+        let keys: Vec<String> = config.keys.iter().map(|n| n.key.to_string()).collect();
+        assert_eq!(keys, vec!["l", "d", "g"]);
+        assert_eq!(config.keys[2].keys[0].id.to_string(), "g s");
+    }
+
+    #[test]
+    fn test_config_include_is_relative_to_including_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(
+            dir.path().join("nested/outer.yml"),
+            "include:\n  - inner.yml\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("nested/inner.yml"),
+            "keys:\n  - key: k\n    value: kubectl\n",
+        )
+        .unwrap();
+
+        let yaml = "include:\n  - nested/outer.yml\n";
+        let config = Config::from_contents(yaml, &dir.path().join(CONFIG_FILE_NAME)).unwrap();
+        assert_eq!(config.keys.len(), 1);
+        assert_eq!(config.keys[0].value, "kubectl");
+    }
+
+    #[test]
+    fn test_config_include_cycle_terminates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &root,
+            "include:\n  - other.yml\nkeys:\n  - key: a\n    value: a\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("other.yml"),
+            format!(
+                "include:\n  - {}\nkeys:\n  - key: b\n    value: b\n",
+                CONFIG_FILE_NAME
+            ),
+        )
+        .unwrap();
+
+        let contents = fs::read_to_string(&root).unwrap();
+        let config = Config::from_contents(&contents, &root).unwrap();
+        assert_eq!(config.keys.len(), 2);
+    }
+
+    #[test]
+    fn test_config_include_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = "include:\n  - missing.yml\n";
+        let result = Config::from_contents(yaml, &dir.path().join(CONFIG_FILE_NAME));
+        assert!(matches!(result, Err(WhichCmdError::ConfigNotFound { .. })));
+    }
+
+    #[test]
+    fn test_config_include_duplicate_keys_reports_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("git.yml"),
+            "keys:\n  - key: g\n    value: git\n",
+        )
+        .unwrap();
+
+        let yaml = r#"
+include:
+  - git.yml
+keys:
+  - key: g
+    value: grep
+"#;
+        let root = dir.path().join(CONFIG_FILE_NAME);
+        let error = Config::from_contents(yaml, &root).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains(&root.display().to_string()));
+        assert!(message.contains(&dir.path().join("git.yml").display().to_string()));
+        match error {
+            WhichCmdError::InvalidConfig(diagnostics) => {
+                let git = dir.path().join("git.yml");
+                assert_eq!(diagnostics[0].location(), format!("{}:2:10", git.display()));
+                assert_eq!(
+                    diagnostics[0].notes,
+                    vec![format!("`g` is first defined at {}:5:10", root.display())]
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_config_nested_duplicate_keys_are_located() {
+        let yaml = "keys:\n  - key: g\n    value: git\n    keys:\n      - key: s\n        value: status\n      - key: s\n        value: stash\n";
+        match Config::from_contents(yaml, Path::new("commands.yml")).unwrap_err() {
+            WhichCmdError::InvalidConfig(diagnostics) => {
+                assert_eq!(diagnostics[0].message, "duplicate key `s`");
+                assert_eq!(diagnostics[0].location(), "commands.yml:7:14");
+                assert_eq!(
+                    diagnostics[0].source_line.as_deref(),
+                    Some("      - key: s")
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    fn ids(ids: &[NodeId]) -> Vec<String> {
+        ids.iter().map(NodeId::to_string).collect()
+    }
+
+    fn apply(config: &mut Config, yaml: &str) {
+        config.apply_layer(yaml, Path::new("")).unwrap();
+    }
+
+    const BASE: &str = r#"
+keys:
+  - key: g
+    value: git
+    keys:
+      - key: s
+        value: status
+      - key: R
+        value: reset
+  - key: l
+    value: ls
+"#;
+
+    #[test]
+    fn test_layer_adds_and_overrides_root_keys() {
+        let mut config = Config::from_contents(BASE, Path::new("")).unwrap();
+        apply(
+            &mut config,
+            r#"
+keys:
+  - key: m
+    value: make
+  - key: l
+    value: eza
+"#,
+        );
+
+        let keys: Vec<String> = config.keys.iter().map(|n| n.key.to_string()).collect();
+        assert_eq!(keys, vec!["g", "l", "m"]);
+        assert_eq!(config.keys[1].value, "eza");
+        assert_eq!(config.layers.len(), 2);
+        assert_eq!(ids(&config.layers[1].overridden), vec!["l"]);
+    }
+
+    #[test]
+    fn test_layer_extends_node_at_key_path() {
+        let mut config = Config::from_contents(BASE, Path::new("")).unwrap();
+        apply(
+            &mut config,
+            r#"
+extend:
+  - path: g
+    keys:
+      - key: s
+        value: status --short
+      - key: x
+        value: clean
+"#,
+        );
+
+        let git = &config.keys[0];
+        assert_eq!(git.keys.len(), 3);
+        assert_eq!(git.keys[0].value, "status --short");
+        assert_eq!(git.keys[2].id.to_string(), "g x");
+        assert_eq!(ids(&config.layers[1].overridden), vec!["g s"]);
+    }
+
+    #[test]
+    fn test_layer_key_paths_use_key_syntax() {
+        let base = "keys:\n  - key: <C-g>\n    value: git\n    keys:\n      - key: s\n        value: status\n";
+        let mut config = Config::from_contents(base, Path::new("")).unwrap();
+        apply(
+            &mut config,
+            r#"
+extend:
+  - path: <c-g>
+    keys:
+      - key: <F1>
+        value: help
+hide:
+  - <C-g> s
+"#,
+        );
+
+        let git = &config.keys[0];
+        assert_eq!(git.keys.len(), 1);
+        assert_eq!(git.keys[0].id.to_string(), "<C-g> <F1>");
+        assert_eq!(ids(&config.layers[1].hidden), vec!["<C-g> s"]);
+    }
+
+    #[test]
+    fn test_layer_hides_nodes() {
+        let mut config = Config::from_contents(BASE, Path::new("")).unwrap();
+        apply(
+            &mut config,
+            r#"
+hide:
+  - g R
+  - l
+  - x y
+"#,
+        );
+
+        assert_eq!(config.keys.len(), 1);
+        assert_eq!(config.keys[0].keys.len(), 1);
+        assert_eq!(ids(&config.layers[1].hidden), vec!["g R", "l"]);
+        assert_eq!(config.layers[1].unmatched, vec!["x y"]);
+    }
+
+    #[test]
+    fn test_layer_duplicate_keys_within_layer() {
+        let mut config = Config::from_contents(BASE, Path::new("")).unwrap();
+        let yaml = r#"
+keys:
+  - key: m
+    value: make
+  - key: m
+    value: meson
+"#;
+        assert!(config.apply_layer(yaml, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_layer_settings_override_earlier_layers() {
+        let yaml = r#"
+settings:
+  bindings:
+    close: <C-c>
+    back: <C-h>
+keys:
+  - key: g
+    value: git
+"#;
+        let mut config = Config::from_contents(yaml, Path::new("")).unwrap();
+        apply(
+            &mut config,
+            "settings:\n  bindings:\n    close: [<Esc>, q]\n",
+        );
+
+        let bindings = &config.settings.bindings;
+        assert_eq!(bindings.close.0.len(), 2);
+        assert_eq!(bindings.back.0, vec!["<C-h>".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_including_file_settings_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("shared.yml"),
+            "settings:\n  bindings:\n    close: <C-c>\n    search: <C-f>\n",
+        )
+        .unwrap();
+        let yaml = "include:\n  - shared.yml\nsettings:\n  bindings:\n    close: <C-q>\n";
+        let config = Config::from_contents(yaml, &dir.path().join(CONFIG_FILE_NAME)).unwrap();
+
+        let bindings = &config.settings.bindings;
+        assert_eq!(bindings.close.0, vec!["<C-q>".parse().unwrap()]);
+        assert_eq!(bindings.search.0, vec!["<C-f>".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_find_project_layers_outermost_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join(PROJECT_CONFIG_FILE_NAME), "keys: []\n").unwrap();
+        fs::write(nested.join(PROJECT_CONFIG_FILE_NAME), "keys: []\n").unwrap();
+
+        let layers = Config::find_project_layers(&nested);
+        let expected_tail = vec![
+            dir.path().join(PROJECT_CONFIG_FILE_NAME),
+            nested.join(PROJECT_CONFIG_FILE_NAME),
+        ];
+        assert!(layers.ends_with(&expected_tail));
+    }
+
+    #[test]
+    fn test_from_file_with_explicit_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.yml");
+        let result = Config::from_file(Some(&missing));
+        assert!(matches!(result, Err(WhichCmdError::ConfigNotFound { .. })));
+    }
+
+    #[test]
+    fn test_from_file_with_explicit_path_ignores_project_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&config_path, "keys:\n  - key: g\n    value: git\n").unwrap();
+        fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE_NAME),
+            "keys:\n  - key: g\n    value: gh\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file_in(Some(&config_path), dir.path()).unwrap();
+        assert_eq!(config.keys[0].value, "git");
+        assert_eq!(config.layers.len(), 1);
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_fields() {
+        let yaml = r#"
+keys:
+  - key: g
+    value: git
+    reset: true
+    keys:
+      - key: s
+        value: status
+        fleting: true
+"#;
+        let error = Config::from_contents(yaml, Path::new("commands.yml")).unwrap_err();
+        match error {
+            WhichCmdError::InvalidConfig(diagnostics) => {
+                assert_eq!(diagnostics.len(), 2);
+                assert_eq!(diagnostics[0].location(), "commands.yml:5:5");
+            }
+            other => panic!("Expected InvalidConfig, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_config_reports_problems_in_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("git.yml"),
+            "keys:\n  - key: g\n    value: git\n    immediate: maybe\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("kubectl.yml"),
+            "keys:\n  - key: k\n    value: kubectl\n",
+        )
+        .unwrap();
+
+        fs::write(
+            dir.path().join("kill.yml"),
+            "keys:\n  - key: k\n    value: kill\n",
+        )
+        .unwrap();
+
+        let yaml = r#"
+include:
+  - git.yml
+  - kubectl.yml
+  - kill.yml
+keys:
+  - key: d
+    value: docker
+    fleting: true
+"#;
+        let root = dir.path().join(CONFIG_FILE_NAME);
+        let error = Config::from_contents(yaml, &root).unwrap_err();
+        let WhichCmdError::InvalidConfig(diagnostics) = error else {
+            panic!("Expected InvalidConfig, got {:?}", error);
+        };
+        let files: Vec<_> = diagnostics
+            .iter()
+            .map(|d| d.path.as_deref().and_then(Path::file_name).unwrap())
+            .collect();
+        assert_eq!(files, [CONFIG_FILE_NAME, "git.yml", "kill.yml"]);
+    }
+
+    #[test]
+    fn test_fixture_config_is_valid() {
+        let contents = include_str!("../fixtures/commands.yml");
+        assert!(Config::from_contents(contents, Path::new("")).is_ok());
+    }
+
+    #[test]
+    fn test_field_lists_match_helpers() {
+        let mut fields = CONFIG_FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(fields, struct_fields::<ConfigHelper>());
+
+        let mut fields = EXTENSION_FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(fields, struct_fields::<Extension>());
     }
 }
@@ -1,9 +1,18 @@
 pub const PREFIX: &str = env!("CARGO_PKG_NAME");
 pub const CONFIG_FILE_NAME: &str = "commands.yml";
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".which-cmd.yml";
 pub const OUTPUT_FILE_NAME: &str = "out";
+pub const ENV_FILE_NAME: &str = "env";
+pub const INPUT_HISTORY_FILE_NAME: &str = "input_history.yml";
+pub const USAGE_FILE_NAME: &str = "usage.yml";
+pub const COMMAND_HISTORY_FILE_NAME: &str = "command_history.yml";
 pub const CHOICE_KEY: &str = "[choice]";
 pub const INPUT_KEY: &str = "[input]";
+pub const CHOICE_PLACEHOLDER: &str = "{choice}";
+pub const INPUT_PLACEHOLDER: &str = "{input}";
 pub const IMMEDIATE_PREFIX: &str = "__IMMEDIATE__";
+/// Shown in place of secret input values
+pub const SECRET_MASK: &str = "••••••";
 
 /// Default height of the TUI content area (excluding borders)
 pub const DEFAULT_HEIGHT: usize = 10;
@@ -11,8 +20,14 @@ pub const DEFAULT_HEIGHT: usize = 10;
 /// Duration to display error messages in the TUI (milliseconds)
 pub const ERROR_DISPLAY_DURATION_MS: u64 = 750;
 
-/// Help text displayed in the TUI footer
-pub fn help_text() -> String {
-    use crossterm::style::Stylize;
-    format!("󱊷  {}  󰁮  {}", "close".dark_grey(), "back".dark_grey())
-}
+/// Time a `choices_cmd` command may run before it is killed (milliseconds)
+pub const CHOICES_CMD_TIMEOUT_MS: u64 = 5000;
+
+/// Number of values kept in the history of each input node
+pub const MAX_INPUT_HISTORY_ENTRIES: usize = 100;
+
+/// Number of nodes and choices whose usage is kept for ranking
+pub const MAX_USAGE_ENTRIES: usize = 1000;
+
+/// Number of built commands kept in the command history
+pub const MAX_COMMAND_HISTORY_ENTRIES: usize = 1000;
@@ -0,0 +1,200 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A range of characters on a single line of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number
+    pub column: usize,
+    /// Number of characters covered, at least 1
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Span {
+            line,
+            column,
+            length: length.max(1),
+        }
+    }
+}
+
+/// A problem found in a configuration file, with the position it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    /// The file the diagnostic refers to
+    pub path: Option<PathBuf>,
+    /// The line of the file that `span` points into
+    pub source_line: Option<String>,
+    /// Additional context, such as where a conflicting key was first defined
+    pub notes: Vec<String>,
+    /// A hint on how to fix the problem
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            path: None,
+            source_line: None,
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Attaches the file the diagnostic was found in, so that it can be
+    /// rendered with the offending line
+    pub fn in_file(mut self, path: &Path, contents: &str) -> Self {
+        if !path.as_os_str().is_empty() {
+            self.path = Some(path.to_path_buf());
+        }
+        self.source_line = contents
+            .lines()
+            .nth(self.span.line.saturating_sub(1))
+            .map(str::to_string);
+        self
+    }
+
+    /// Formats the position as `path:line:column`
+    pub fn location(&self) -> String {
+        format_location(self.path.as_deref().unwrap_or(Path::new("")), self.span)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.span.line.to_string().len());
+        write!(
+            f,
+            "error: {}\n{} --> {}",
+            self.message,
+            gutter,
+            self.location()
+        )?;
+
+        if let Some(source_line) = &self.source_line {
+            // Keep tabs in the indentation so that the underline stays aligned
+            let indent: String = source_line
+                .chars()
+                .take(self.span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            write!(
+                f,
+                "\n{} |\n{} | {}\n{} | {}{}",
+                gutter,
+                self.span.line,
+                source_line,
+                gutter,
+                indent,
+                "^".repeat(self.span.length)
+            )?;
+        }
+
+        for note in &self.notes {
+            write!(f, "\n{} = note: {}", gutter, note)?;
+        }
+        if let Some(help) = &self.help {
+            write!(f, "\n{} = help: {}", gutter, help)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a position as `path:line:column`, or `line:column` without a path
+pub fn format_location(path: &Path, span: Span) -> String {
+    if path.as_os_str().is_empty() {
+        format!("{}:{}", span.line, span.column)
+    } else {
+        format!("{}:{}:{}", path.display(), span.line, span.column)
+    }
+}
+
+/// Converts a deserialization error into a diagnostic, if it has a location
+pub fn from_yaml_error(error: &serde_yaml::Error) -> Option<Diagnostic> {
+    let location = error.location()?;
+    let message = error.to_string().replacen(
+        &format!(" at line {} column {}", location.line(), location.column()),
+        "",
+        1,
+    );
+    Some(Diagnostic::new(
+        message,
+        Span::new(location.line(), location.column(), 1),
+    ))
+}
+
+/// Formats a list of diagnostics, separated by blank lines
+pub fn format_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diagnostic = Diagnostic::new("unknown field `reset`", Span::new(12, 9, 5));
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: unknown field `reset`\n   --> 12:9"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_display_with_help() {
+        let diagnostic = Diagnostic::new("unknown field `reset`", Span::new(12, 9, 5))
+            .with_help("did you mean `anchor`?");
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: unknown field `reset`\n   --> 12:9\n   = help: did you mean `anchor`?"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_display_with_snippet() {
+        let contents = "keys:\n  - key: s\n    value: status\n  - key: s\n    value: stash\n";
+        let diagnostic = Diagnostic::new("duplicate key `s`", Span::new(4, 10, 1))
+            .with_note("`s` is first defined at 2:10")
+            .in_file(Path::new("commands.yml"), contents);
+
+        let expected = "\
+error: duplicate key `s`
+  --> commands.yml:4:10
+  |
+4 |   - key: s
+  |          ^
+  = note: `s` is first defined at 2:10";
+        assert_eq!(diagnostic.to_string(), expected);
+    }
+
+    #[test]
+    fn test_from_yaml_error_strips_location_from_message() {
+        let error = serde_yaml::from_str::<Vec<String>>("- a\n- b: c\n").unwrap_err();
+        let diagnostic = from_yaml_error(&error).unwrap();
+        assert_eq!(diagnostic.span.line, 2);
+        assert!(!diagnostic.message.contains("at line"));
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::diagnostic::{format_diagnostics, Diagnostic};
+
 /// Custom error type for which-cmd operations
 #[derive(Error, Debug)]
 pub enum WhichCmdError {
@@ -11,10 +13,22 @@ pub enum WhichCmdError {
     #[error("Conflicting keys found: {0}")]
     ConflictingKeys(String),
 
+    /// Configuration files contain problems at known locations
+    #[error("Invalid configuration:\n\n{}", format_diagnostics(.0))]
+    InvalidConfig(Vec<Diagnostic>),
+
     /// Invalid YAML in configuration file
     #[error("Failed to parse configuration: {0}")]
     ConfigParse(#[from] serde_yaml::Error),
 
+    /// A project-local configuration layer could not be applied
+    #[error("Invalid configuration layer {path}: {message}")]
+    InvalidLayer { path: String, message: String },
+
+    /// Invalid glob in an `include` directive
+    #[error("Invalid include pattern: {0}")]
+    IncludePattern(#[from] glob::PatternError),
+
     /// IO error reading configuration
     #[error("Failed to read configuration: {0}")]
     ConfigIo(#[from] std::io::Error),
@@ -23,6 +37,10 @@ pub enum WhichCmdError {
     #[error("Failed to access XDG directories: {0}")]
     Xdg(#[from] xdg::BaseDirectoriesError),
 
+    /// A `choices_cmd` command failed, timed out or produced no output
+    #[error("Choices command failed: {0}")]
+    ChoicesCommand(String),
+
     /// Terminal operation failed
     #[error("Terminal error: {0}")]
     Terminal(String),
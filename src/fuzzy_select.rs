@@ -1,30 +1,59 @@
 use crate::error::Result;
+use crate::line_editor::{typed_char, Edit, LineEditor};
+use crate::matcher::{MatchMode, Matcher};
+use crate::settings::Bindings;
 use crate::terminal::Terminal;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
+    event::{self, Event},
     style::Stylize,
 };
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
 use std::io::Write;
 
 /// A fuzzy select interface that integrates with our bordered TUI
 pub struct FuzzySelect<'a> {
     items: &'a [String],
     prompt: String,
+    bindings: Bindings,
+    match_mode: MatchMode,
+    /// Added to the match scores of the items at the same index
+    boosts: Vec<i64>,
+    /// The query typed when the select opens
+    query: String,
+    /// What switching the scope does, shown in the footer, if the scope
+    /// binding ends the select
+    scope_switch: Option<String>,
+}
+
+/// How a fuzzy select ended
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome<T> {
+    Selected(T),
+    Cancelled,
+    /// The scope binding was pressed, with the query typed so far
+    SwitchedScope(String),
 }
 
 struct MatchedItem {
     index: usize,
     score: i64,
     text: String,
+    /// Positions of the matched characters in `text`, counted in chars
+    positions: Vec<usize>,
 }
 
+/// Rows taken by the prompt, the padding around the items and the footer
+const NON_ITEM_ROWS: usize = 4;
+
 impl<'a> FuzzySelect<'a> {
     pub fn new(items: &'a [String]) -> Self {
         FuzzySelect {
             items,
             prompt: " :".to_string(),
+            bindings: Bindings::default(),
+            match_mode: MatchMode::default(),
+            boosts: Vec::new(),
+            query: String::new(),
+            scope_switch: None,
         }
     }
 
@@ -33,101 +62,201 @@ impl<'a> FuzzySelect<'a> {
         self
     }
 
+    pub fn with_bindings(mut self, bindings: &Bindings) -> Self {
+        self.bindings = bindings.clone();
+        self
+    }
+
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
+
+    /// Ranks items by `boosts` as well as by how well they match. Without a
+    /// query, items are listed by boost, and otherwise in their own order.
+    pub fn with_boosts(mut self, boosts: Vec<i64>) -> Self {
+        self.boosts = boosts;
+        self
+    }
+
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = query.to_string();
+        self
+    }
+
+    /// Lets the scope binding end the select, for the caller to show other
+    /// items. `action` describes the switch in the footer.
+    pub fn with_scope_switch(mut self, action: &str) -> Self {
+        self.scope_switch = Some(action.to_string());
+        self
+    }
+
     /// Run the fuzzy select interface and return the selected index, or None if cancelled
     pub fn interact<W: Write>(&mut self, terminal: &mut Terminal<W>) -> Result<Option<usize>> {
-        let mut query = String::new();
-        let mut cursor_pos = 0usize;
+        match self.interact_scoped(terminal)? {
+            Outcome::Selected(index) => Ok(Some(index)),
+            Outcome::Cancelled | Outcome::SwitchedScope(_) => Ok(None),
+        }
+    }
+
+    /// Run the fuzzy select interface, which the scope binding can also end
+    /// if a scope switch was set
+    pub fn interact_scoped<W: Write>(
+        &mut self,
+        terminal: &mut Terminal<W>,
+    ) -> Result<Outcome<usize>> {
+        Ok(match self.run(terminal, false)? {
+            Outcome::Selected(indices) => match indices.first() {
+                Some(&index) => Outcome::Selected(index),
+                None => Outcome::Cancelled,
+            },
+            Outcome::Cancelled => Outcome::Cancelled,
+            Outcome::SwitchedScope(query) => Outcome::SwitchedScope(query),
+        })
+    }
+
+    /// Run the fuzzy select interface allowing several items to be toggled,
+    /// and return their indices in the order of the items, or None if
+    /// cancelled. Confirming without toggling any selects the highlighted item.
+    pub fn interact_multi<W: Write>(
+        &mut self,
+        terminal: &mut Terminal<W>,
+    ) -> Result<Option<Vec<usize>>> {
+        match self.run(terminal, true)? {
+            Outcome::Selected(indices) => Ok(Some(indices)),
+            Outcome::Cancelled | Outcome::SwitchedScope(_) => Ok(None),
+        }
+    }
+
+    fn run<W: Write>(
+        &mut self,
+        terminal: &mut Terminal<W>,
+        multi: bool,
+    ) -> Result<Outcome<Vec<usize>>> {
+        let mut query = LineEditor::default();
+        query.set_text(self.query.as_str());
         let mut selected_index = 0usize;
+        // The first matched item shown, moved to keep the selection visible
+        let mut scroll_offset = 0usize;
+        // Indices of the items toggled so far
+        let mut toggled: Vec<usize> = Vec::new();
 
         // Show cursor at the start
         terminal.show_cursor()?;
 
         loop {
             // Filter and sort items based on current query
-            let matched_items = self.filter_items(&query);
+            let matched_items = self.filter_items(query.text());
 
             // Ensure selected_index is within bounds
             if selected_index >= matched_items.len() && !matched_items.is_empty() {
                 selected_index = matched_items.len() - 1;
             }
+            let visible_rows = terminal.get_content_rows().saturating_sub(NON_ITEM_ROWS);
+            scroll_offset = scroll_to_selection(scroll_offset, selected_index, visible_rows);
 
             // Render the interface
-            self.render(terminal, &query, &matched_items, selected_index)?;
+            self.render(
+                terminal,
+                &query,
+                &matched_items,
+                selected_index,
+                scroll_offset,
+                multi.then_some(toggled.as_slice()),
+            )?;
 
             // Wait for input
-            if let Event::Key(KeyEvent { code, .. }) = event::read().map_err(|e| {
+            if let Event::Key(event) = event::read().map_err(|e| {
                 crate::error::WhichCmdError::Terminal(format!("Failed to read event: {}", e))
             })? {
-                match code {
-                    KeyCode::Esc => {
-                        terminal.hide_cursor()?;
-                        return Ok(None);
+                if self.bindings.close.matches(&event) {
+                    terminal.hide_cursor()?;
+                    return Ok(Outcome::Cancelled);
+                } else if self.bindings.execute.matches(&event) {
+                    terminal.hide_cursor()?;
+                    if !toggled.is_empty() {
+                        toggled.sort_unstable();
+                        return Ok(Outcome::Selected(toggled));
                     }
-                    KeyCode::Enter => {
-                        terminal.hide_cursor()?;
-                        if matched_items.is_empty() {
-                            return Ok(None);
-                        }
-                        return Ok(Some(matched_items[selected_index].index));
+                    if matched_items.is_empty() {
+                        return Ok(Outcome::Cancelled);
                     }
-                    KeyCode::Char(c) => {
-                        query.insert(cursor_pos, c);
-                        cursor_pos += 1;
-                        selected_index = 0; // Reset selection when query changes
+                    return Ok(Outcome::Selected(vec![matched_items[selected_index].index]));
+                } else if self.scope_switch.is_some() && self.bindings.scope.matches(&event) {
+                    terminal.hide_cursor()?;
+                    return Ok(Outcome::SwitchedScope(query.text().to_string()));
+                } else if self.bindings.prev.matches(&event) {
+                    selected_index = selected_index.saturating_sub(1);
+                    continue;
+                } else if self.bindings.next.matches(&event) {
+                    if !matched_items.is_empty() && selected_index < matched_items.len() - 1 {
+                        selected_index += 1;
                     }
-                    KeyCode::Backspace => {
-                        if cursor_pos > 0 {
-                            query.remove(cursor_pos - 1);
-                            cursor_pos -= 1;
-                            selected_index = 0;
+                    continue;
+                } else if self.bindings.page_up.matches(&event) {
+                    selected_index = selected_index.saturating_sub(visible_rows.max(1));
+                    continue;
+                } else if self.bindings.page_down.matches(&event) {
+                    selected_index = (selected_index + visible_rows.max(1))
+                        .min(matched_items.len().saturating_sub(1));
+                    continue;
+                } else if multi && self.bindings.toggle.matches(&event) {
+                    if let Some(item) = matched_items.get(selected_index) {
+                        match toggled.iter().position(|&index| index == item.index) {
+                            Some(position) => {
+                                toggled.remove(position);
+                            }
+                            None => toggled.push(item.index),
                         }
-                    }
-                    KeyCode::Up => {
-                        selected_index = selected_index.saturating_sub(1);
-                    }
-                    KeyCode::Down => {
-                        if !matched_items.is_empty() && selected_index < matched_items.len() - 1 {
+                        // Move on, so that consecutive items can be toggled quickly
+                        if selected_index + 1 < matched_items.len() {
                             selected_index += 1;
                         }
                     }
-                    _ => {}
+                    continue;
+                }
+
+                match query.handle(&event, &self.bindings.delete_word) {
+                    // Reset selection when query changes
+                    Edit::Changed => selected_index = 0,
+                    Edit::Moved => {}
+                    Edit::Ignored => {
+                        if let Some(c) = typed_char(&event) {
+                            query.insert(c);
+                            selected_index = 0;
+                        }
+                    }
                 }
             }
         }
     }
 
-    /// Filter items based on query using fuzzy matching
+    /// Filter items based on query using the match mode, and sort them by
+    /// score and boost
     fn filter_items(&self, query: &str) -> Vec<MatchedItem> {
-        if query.is_empty() {
-            // No query, return all items in original order
-            return self
-                .items
-                .iter()
-                .enumerate()
-                .map(|(index, text)| MatchedItem {
-                    index,
-                    score: 0,
-                    text: text.clone(),
-                })
-                .collect();
-        }
-
-        let matcher = SkimMatcherV2::default();
+        let matcher = Matcher::new(self.match_mode);
         let mut matched: Vec<MatchedItem> = self
             .items
             .iter()
             .enumerate()
             .filter_map(|(index, text)| {
-                matcher.fuzzy_match(text, query).map(|score| MatchedItem {
+                // No query matches every item
+                let (score, positions) = if query.is_empty() {
+                    (0, Vec::new())
+                } else {
+                    matcher.match_item(text, query)?
+                };
+                Some(MatchedItem {
                     index,
-                    score,
+                    score: score + self.boosts.get(index).copied().unwrap_or(0),
                     text: text.clone(),
+                    positions,
                 })
             })
             .collect();
 
-        // Sort by score (highest first)
-        matched.sort_by(|a, b| b.score.cmp(&a.score));
+        // Sort by score (highest first), keeping the order of equal items
+        matched.sort_by_key(|item| std::cmp::Reverse(item.score));
 
         matched
     }
@@ -144,15 +273,27 @@ impl<'a> FuzzySelect<'a> {
     fn render<W: Write>(
         &self,
         terminal: &mut Terminal<W>,
-        query: &str,
+        query: &LineEditor,
         matched_items: &[MatchedItem],
         selected_index: usize,
+        scroll_offset: usize,
+        toggled: Option<&[usize]>,
     ) -> Result<()> {
         // Clear screen and draw top border
         terminal.clear_screen()?;
 
-        // Line 2: Prompt and query
-        terminal.write_line(&format!("{} {}", self.prompt.clone().yellow(), query))?;
+        // Line 2: Prompt and query, with the number of matches on the right
+        let prompt_line = format!("{} {}", self.prompt.clone().yellow(), query.text());
+        let counter = format!("{}/{}", matched_items.len(), self.items.len());
+        let padding = terminal
+            .get_content_width()
+            .saturating_sub(console::measure_text_width(&prompt_line) + counter.len());
+        terminal.write_line(&format!(
+            "{}{}{}",
+            prompt_line,
+            " ".repeat(padding),
+            counter.dark_grey()
+        ))?;
 
         // Line 3: Empty padding
         terminal.empty_border_line()?;
@@ -164,18 +305,21 @@ impl<'a> FuzzySelect<'a> {
         // - N lines: items
         // - 1 line: empty padding after items
         // - 1 line: footer
-        let content_rows = terminal.get_content_rows();
-        let prompt_and_header_lines = 2; // prompt + padding
-        let footer_lines = 2; // padding + help text
-        let num_items = content_rows.saturating_sub(prompt_and_header_lines + footer_lines);
-
-        for i in 0..num_items {
-            if i < matched_items.len() {
-                let item = &matched_items[i];
+        let num_items = terminal.get_content_rows().saturating_sub(NON_ITEM_ROWS);
+
+        for i in scroll_offset..scroll_offset + num_items {
+            if let Some(item) = matched_items.get(i) {
+                // Lists that allow several items mark the toggled ones
+                let marker = match toggled {
+                    Some(toggled) if toggled.contains(&item.index) => format!("{} ", "●".green()),
+                    Some(_) => format!("{} ", "○".dark_grey()),
+                    None => String::new(),
+                };
+                let text = highlight_matches(&item.text, &item.positions);
                 let display = if i == selected_index {
-                    format!("{} {}", ">".yellow(), item.text.clone())
+                    format!("{} {}{}", ">".yellow(), marker, text)
                 } else {
-                    format!("  {}", item.text)
+                    format!("  {}{}", marker, text)
                 };
                 terminal.write_line(&display)?;
             } else {
@@ -188,25 +332,135 @@ impl<'a> FuzzySelect<'a> {
         terminal.empty_border_line()?;
 
         // Footer
-        terminal.write_centered(&format!("󱊷  {}", "cancel".dark_grey()))?;
+        let help_text = match (toggled, &self.scope_switch) {
+            (Some(_), _) => self.bindings.multi_select_text(),
+            (None, Some(action)) => self.bindings.scope_switch_text(action),
+            (None, None) => self.bindings.cancel_text(),
+        };
+        terminal.write_centered(&help_text)?;
 
         // Bottom border
         terminal.draw_bottom_border()?;
 
         terminal.flush()?;
 
-        // Position cursor after the query text
+        // Position cursor within the query text
         // Row: start_row + 1 (accounting for top border if present)
-        // Col: border (2 chars "│ ") + prompt length + space + query length
+        // Col: border (2 chars "│ ") + prompt length + space + width of query before the cursor
         let row = terminal.get_start_row() + if terminal.has_border() { 1 } else { 0 }; // Line 2 (0-indexed, so +1 from start)
         let prompt_len = console::measure_text_width(&self.prompt);
-        let query_len = console::measure_text_width(query);
         let col = if terminal.has_border() { 2 } else { 1 }
             + prompt_len as u16
             + 1 // for the space after prompt
-            + query_len as u16;
+            + query.cursor_width() as u16;
         terminal.move_cursor_to(col, row)?;
 
         Ok(())
     }
 }
+
+/// Moves the first visible row `offset` just far enough for `selected` to be
+/// among the `visible` rows shown
+fn scroll_to_selection(offset: usize, selected: usize, visible: usize) -> usize {
+    if visible == 0 || selected < offset {
+        selected
+    } else if selected >= offset + visible {
+        selected + 1 - visible
+    } else {
+        offset
+    }
+}
+
+/// Highlights the characters of `text` at `positions`
+fn highlight_matches(text: &str, positions: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if positions.contains(&i) {
+                c.yellow().bold().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_items_keeps_match_positions() {
+        let items = vec!["git checkout".to_string(), "kubectl get pods".to_string()];
+        let select = FuzzySelect::new(&items);
+        let matched = select.filter_items("gco");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].index, 0);
+        assert_eq!(matched[0].positions, vec![0, 4, 9]);
+
+        assert!(select.filter_items("")[1].positions.is_empty());
+
+        let select = FuzzySelect::new(&items).with_match_mode(MatchMode::Prefix);
+        let matched = select.filter_items("kube");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].index, 1);
+    }
+
+    #[test]
+    fn test_filter_items_blends_boosts() {
+        let items = vec![
+            "git status".to_string(),
+            "git stash".to_string(),
+            "git show".to_string(),
+        ];
+        let select = FuzzySelect::new(&items).with_boosts(vec![0, 0, 40]);
+        let order = |query| {
+            select
+                .filter_items(query)
+                .iter()
+                .map(|item| item.index)
+                .collect::<Vec<_>>()
+        };
+        // Without a query, boosted items come first and the rest keep their order
+        assert_eq!(order(""), vec![2, 0, 1]);
+        // Boosts still apply among the matches
+        assert_eq!(order("git s")[0], 2);
+        assert_eq!(order("stash"), vec![1]);
+    }
+
+    #[test]
+    fn test_filter_items_blends_boosts_in_exact_mode() {
+        let order = |items: &[&str], boosts| {
+            let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+            FuzzySelect::new(&items)
+                .with_match_mode(MatchMode::Exact)
+                .with_boosts(boosts)
+                .filter_items("stash")
+                .iter()
+                .map(|item| item.index)
+                .collect::<Vec<_>>()
+        };
+        // A boost lifts a slightly worse match above a better one
+        assert_eq!(order(&["git stash", "git stash list"], vec![0, 20]), [1, 0]);
+        // but not above a much better one
+        assert_eq!(order(&["stash", "git stash list"], vec![0, 20]), [0, 1]);
+    }
+
+    #[test]
+    fn test_highlight_matches_keeps_text() {
+        let highlighted = highlight_matches("ünïcode", &[0, 2]);
+        assert_eq!(console::strip_ansi_codes(&highlighted), "ünïcode");
+    }
+
+    #[test]
+    fn test_scroll_to_selection() {
+        // Within the window, nothing moves
+        assert_eq!(scroll_to_selection(0, 3, 5), 0);
+        // Moving past the bottom scrolls down by as much as needed
+        assert_eq!(scroll_to_selection(0, 5, 5), 1);
+        assert_eq!(scroll_to_selection(10, 40, 5), 36);
+        // Moving above the top scrolls up to the selection
+        assert_eq!(scroll_to_selection(10, 7, 5), 7);
+        assert_eq!(scroll_to_selection(3, 0, 0), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use crate::constants::{INPUT_HISTORY_FILE_NAME, MAX_INPUT_HISTORY_ENTRIES};
+use crate::error::Result;
+use crate::node_id::NodeId;
+use crate::store::YamlStore;
+
+/// Values previously entered at input nodes, kept per node.
+///
+/// The history is stored as YAML in the XDG data directory, keyed by node
+/// id with the most recent entry first, e.g.
+///
+/// ```yaml
+/// g p:
+///   - origin
+///   - upstream
+/// ```
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    store: YamlStore<BTreeMap<String, Vec<String>>>,
+}
+
+impl InputHistory {
+    /// Opens the history in the XDG data directory
+    pub fn open() -> Self {
+        InputHistory {
+            store: YamlStore::open(INPUT_HISTORY_FILE_NAME),
+        }
+    }
+
+    /// The values entered at the node with `id`, most recent first
+    pub fn entries(&self, id: &NodeId) -> &[String] {
+        self.store
+            .data
+            .get(&id.to_string())
+            .map_or(&[], |entries| entries.as_slice())
+    }
+
+    /// Records `value` as the most recent entry of the node with `id`,
+    /// moving it to the front if it was entered before
+    pub fn record(&mut self, id: &NodeId, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let entries = self.store.data.entry(id.to_string()).or_default();
+        entries.retain(|entry| entry != value);
+        entries.insert(0, value.to_string());
+        entries.truncate(MAX_INPUT_HISTORY_ENTRIES);
+    }
+
+    /// Writes the history to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        self.store.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn load(path: PathBuf) -> InputHistory {
+        InputHistory {
+            store: YamlStore::load(path),
+        }
+    }
+
+    fn id(s: &str) -> NodeId {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_record_puts_most_recent_first() {
+        let mut history = InputHistory::default();
+        let remote = id("g p");
+        history.record(&remote, "origin");
+        history.record(&remote, "upstream");
+        history.record(&remote, "origin");
+        history.record(&remote, "");
+        assert_eq!(history.entries(&remote), ["origin", "upstream"]);
+        assert!(history.entries(&id("g c")).is_empty());
+    }
+
+    #[test]
+    fn test_record_keeps_a_limited_number_of_entries() {
+        let mut history = InputHistory::default();
+        let node = id("k n");
+        for i in 0..MAX_INPUT_HISTORY_ENTRIES + 5 {
+            history.record(&node, &i.to_string());
+        }
+        let entries = history.entries(&node);
+        assert_eq!(entries.len(), MAX_INPUT_HISTORY_ENTRIES);
+        assert_eq!(
+            entries[0],
+            (MAX_INPUT_HISTORY_ENTRIES + 4).to_string(),
+            "the oldest entries are dropped"
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INPUT_HISTORY_FILE_NAME);
+
+        let mut history = load(path.clone());
+        history.record(&id("g <C-b>"), "feature/login");
+        history.save().unwrap();
+        // Keyed by the id of the input node itself, as the TUI records it
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "g <C-b>:\n- feature/login\n"
+        );
+
+        let loaded = load(path);
+        assert_eq!(loaded.entries(&id("g <C-b>")), ["feature/login"]);
+    }
+
+    #[test]
+    fn test_load_ignores_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INPUT_HISTORY_FILE_NAME);
+        fs::write(&path, "- not: [a map").unwrap();
+        let history = load(path);
+        assert!(history.entries(&id("g")).is_empty());
+    }
+}
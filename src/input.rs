@@ -1,16 +1,38 @@
 use crate::error::{Result, WhichCmdError};
-use crate::node::InputType;
+use crate::fuzzy_select::FuzzySelect;
+use crate::input_type::{common_prefix, InputType};
+use crate::line_editor::{typed_char, Edit, LineEditor};
+use crate::matcher::MatchMode;
+use crate::settings::Bindings;
 use crate::terminal::Terminal;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
+    event::{self, Event, KeyCode},
     style::Stylize,
 };
 use std::io::Write;
 
+/// Completions beyond this many are summarised rather than listed
+const MAX_LISTED_COMPLETIONS: usize = 8;
+
+/// A line shown right below the input
+enum Notice {
+    /// The value failed validation
+    Error(String),
+    /// Tab found several completions
+    Completions(Vec<String>),
+}
+
 /// An input component that integrates with our bordered TUI
 pub struct Input<'a> {
     input_type: &'a InputType,
     prompt: String,
+    bindings: Bindings,
+    /// How the history is searched
+    match_mode: MatchMode,
+    default: Option<String>,
+    placeholder: Option<String>,
+    /// Values previously entered at this prompt, most recent first
+    history: Vec<String>,
 }
 
 impl<'a> Input<'a> {
@@ -18,87 +40,201 @@ impl<'a> Input<'a> {
         Input {
             input_type,
             prompt: format!("Enter {}: ", name),
+            bindings: Bindings::default(),
+            match_mode: MatchMode::default(),
+            default: None,
+            placeholder: None,
+            history: Vec::new(),
         }
     }
 
-    /// Run the input interface and return the entered value, or None if cancelled
+    pub fn with_bindings(mut self, bindings: &Bindings) -> Self {
+        self.bindings = bindings.clone();
+        self
+    }
+
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
+
+    /// Sets the value entered when the input is confirmed while empty
+    pub fn with_default(mut self, default: Option<&str>) -> Self {
+        self.default = default.map(str::to_string);
+        self
+    }
+
+    /// Sets the hint shown in the empty input when there is no default
+    pub fn with_placeholder(mut self, placeholder: Option<&str>) -> Self {
+        self.placeholder = placeholder.map(str::to_string);
+        self
+    }
+
+    /// Sets the values previously entered at this prompt, most recent first
+    pub fn with_history(mut self, history: &[String]) -> Self {
+        self.history = history.to_vec();
+        self
+    }
+
+    /// Run the input interface and return the entered value, or None if cancelled.
+    /// Confirming an empty input enters the default, if there is one. Earlier
+    /// values can be recalled with the prev and next bindings, or searched.
+    /// Values that fail validation are not returned; the problem is shown below
+    /// the prompt until the input changes. Tab completes paths and enum values.
     pub fn interact<W: Write>(&self, terminal: &mut Terminal<W>) -> Result<Option<String>> {
-        let mut input_str = String::new();
+        let mut line = LineEditor::default();
+        let mut notice: Option<Notice> = None;
+        let mut history = HistoryCursor::new(&self.history);
 
         // Render initial state
-        self.render(terminal, &input_str)?;
+        self.render(terminal, &line, notice.as_ref())?;
 
         // Enable cursor
         terminal.show_cursor()?;
 
         loop {
             // Wait for input
-            if let Event::Key(KeyEvent { code, .. }) = event::read()
+            if let Event::Key(event) = event::read()
                 .map_err(|e| WhichCmdError::Terminal(format!("Failed to read event: {}", e)))?
             {
-                match code {
-                    KeyCode::Enter => {
-                        terminal.hide_cursor()?;
-                        // Validate number input
-                        if let InputType::Number = self.input_type {
-                            if input_str.is_empty() {
-                                return Ok(None);
-                            }
-                            input_str.parse::<i32>().map_err(|_| {
-                                WhichCmdError::Terminal("Invalid number".to_string())
-                            })?;
-                        }
-                        return Ok(Some(input_str));
-                    }
-                    KeyCode::Esc => {
+                if self.bindings.execute.matches(&event) {
+                    let value = match &self.default {
+                        Some(default) if line.is_empty() => default.clone(),
+                        _ => line.text().to_string(),
+                    };
+                    // An empty number is treated as cancelling the input
+                    if self.input_type.is_numeric() && value.is_empty() {
                         terminal.hide_cursor()?;
                         return Ok(None);
                     }
-                    KeyCode::Char(c) => {
-                        // Validate input based on type
-                        match self.input_type {
-                            InputType::Number => {
-                                // Only allow digits and minus sign (at start)
-                                if c.is_ascii_digit() || (c == '-' && input_str.is_empty()) {
-                                    input_str.push(c);
-                                    self.render(terminal, &input_str)?;
-                                }
-                            }
-                            InputType::Text => {
-                                input_str.push(c);
-                                self.render(terminal, &input_str)?;
-                            }
+                    match self.input_type.validate(&value) {
+                        Ok(()) => {
+                            terminal.hide_cursor()?;
+                            return Ok(Some(value));
                         }
+                        Err(message) => notice = Some(Notice::Error(message)),
                     }
-                    KeyCode::Backspace => {
-                        if !input_str.is_empty() {
-                            input_str.pop();
-                            self.render(terminal, &input_str)?;
-                        }
+                } else if self.bindings.close.matches(&event) {
+                    terminal.hide_cursor()?;
+                    return Ok(None);
+                } else if self.bindings.prev.matches(&event) {
+                    match history.older(line.text()) {
+                        Some(entry) => line.set_text(entry),
+                        None => continue,
+                    }
+                    notice = None;
+                } else if self.bindings.next.matches(&event) {
+                    match history.newer() {
+                        Some(entry) => line.set_text(entry),
+                        None => continue,
+                    }
+                    notice = None;
+                } else if self.bindings.history.matches(&event) {
+                    if self.history.is_empty() {
+                        continue;
+                    }
+                    let mut search = FuzzySelect::new(&self.history)
+                        .with_prompt("History:")
+                        .with_bindings(&self.bindings)
+                        .with_match_mode(self.match_mode);
+                    if let Some(index) = search.interact(terminal)? {
+                        line.set_text(self.history[index].as_str());
+                        notice = None;
+                    }
+                    terminal.show_cursor()?;
+                } else {
+                    match line.handle(&event, &self.bindings.delete_word) {
+                        Edit::Changed => notice = None,
+                        Edit::Moved => {}
+                        Edit::Ignored => match (typed_char(&event), event.code) {
+                            (Some(c), _)
+                                if self.input_type.accepts_char(line.before_cursor(), c) =>
+                            {
+                                line.insert(c);
+                                notice = None;
+                            }
+                            (None, KeyCode::Tab) => notice = self.complete(&mut line),
+                            _ => continue,
+                        },
                     }
-                    _ => {}
                 }
+                self.render(terminal, &line, notice.as_ref())?;
             }
         }
     }
 
+    /// Completes the input as far as all completions agree, and lists them
+    /// when there is more than one
+    fn complete(&self, line: &mut LineEditor) -> Option<Notice> {
+        let completions = self.input_type.completions(line.text());
+        let prefix = common_prefix(&completions);
+        if prefix.len() > line.text().len() {
+            line.set_text(prefix);
+        }
+        (completions.len() > 1).then_some(Notice::Completions(completions))
+    }
+
     /// Render the input interface
-    fn render<W: Write>(&self, terminal: &mut Terminal<W>, input: &str) -> Result<()> {
+    fn render<W: Write>(
+        &self,
+        terminal: &mut Terminal<W>,
+        line: &LineEditor,
+        notice: Option<&Notice>,
+    ) -> Result<()> {
         terminal.clear_screen()?;
 
-        // Display prompt and input
-        terminal.write_line(&format!("{}{}", self.prompt.clone().cyan(), input))?;
+        // Display prompt and input, or the default or placeholder dimmed while
+        // the input is empty. Secrets are shown as bullets.
+        let masked;
+        let line = if self.input_type.is_secret() {
+            masked = line.masked('•');
+            &masked
+        } else {
+            line
+        };
+        let hint = self.default.as_ref().or(self.placeholder.as_ref());
+        let shown = match hint {
+            Some(hint) if line.is_empty() => format!("{}", hint.as_str().dark_grey()),
+            _ => line.text().to_string(),
+        };
+        terminal.write_line(&format!("{}{}", self.prompt.clone().cyan(), shown))?;
+
+        // Display the validation error or completions, if any, right below the input
+        let notice_lines = match notice {
+            Some(Notice::Error(message)) => {
+                terminal.write_line(&format!("{}", message.as_str().red()))?;
+                1
+            }
+            Some(Notice::Completions(completions)) => {
+                let mut listed = completions
+                    .iter()
+                    .take(MAX_LISTED_COMPLETIONS)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                if completions.len() > MAX_LISTED_COMPLETIONS {
+                    listed.push_str(&format!(
+                        "  (+{} more)",
+                        completions.len() - MAX_LISTED_COMPLETIONS
+                    ));
+                }
+                terminal.write_line(&format!("{}", listed.dark_grey()))?;
+                1
+            }
+            None => 0,
+        };
 
         // Fill remaining space dynamically based on terminal content rows
         // Input view layout:
         // - 1 line: prompt + input (already written above)
+        // - 0-1 lines: validation error or completions (already written above)
         // - N lines: empty filler space
         // - 1 line: empty line before footer
         // - 1 line: footer with help text
         let content_rows = terminal.get_content_rows();
         let prompt_lines = 1;
         let footer_lines = 2; // empty + help text
-        let filler_lines = content_rows.saturating_sub(prompt_lines + footer_lines);
+        let filler_lines = content_rows.saturating_sub(prompt_lines + notice_lines + footer_lines);
 
         // Fill the filler area
         for _ in 0..filler_lines {
@@ -107,23 +243,86 @@ impl<'a> Input<'a> {
 
         // Footer
         terminal.empty_border_line()?;
-        terminal.write_centered(&format!("󱊷  {}", "cancel".dark_grey()))?;
+        terminal.write_centered(&self.bindings.cancel_text())?;
 
         terminal.draw_bottom_border()?;
         terminal.flush()?;
 
-        // Position cursor after the input text
+        // Position cursor within the input text
         // Row: start_row + 1 (line with prompt/input, accounting for top border)
-        // Col: border (2 chars "│ ") + prompt length + input length
+        // Col: border (2 chars "│ ") + prompt length + width of text before the cursor
         let row = terminal.get_start_row() + if terminal.has_border() { 1 } else { 0 };
         let prompt_len = console::measure_text_width(&self.prompt);
-        let input_len = console::measure_text_width(input);
         let col = if terminal.has_border() { 1 } else { 0 }
             + prompt_len as u16
             + 1 // for the space after prompt
-            + input_len as u16;
+            + line.cursor_width() as u16;
         terminal.move_cursor_to(col, row)?;
 
         Ok(())
     }
 }
+
+/// Steps through the history of a prompt. What was typed before the first
+/// step is kept, and comes back when stepping past the newest entry.
+struct HistoryCursor<'h> {
+    entries: &'h [String],
+    /// The entry shown, or `None` while editing the draft
+    position: Option<usize>,
+    draft: String,
+}
+
+impl<'h> HistoryCursor<'h> {
+    fn new(entries: &'h [String]) -> Self {
+        HistoryCursor {
+            entries,
+            position: None,
+            draft: String::new(),
+        }
+    }
+
+    /// Moves to the next older entry, if any, saving `current` as the draft
+    /// when leaving it
+    fn older(&mut self, current: &str) -> Option<&str> {
+        let next = self.position.map_or(0, |position| position + 1);
+        let entry = self.entries.get(next)?;
+        if self.position.is_none() {
+            self.draft = current.to_string();
+        }
+        self.position = Some(next);
+        Some(entry)
+    }
+
+    /// Moves to the next newer entry, or back to the draft
+    fn newer(&mut self) -> Option<&str> {
+        match self.position? {
+            0 => {
+                self.position = None;
+                Some(&self.draft)
+            }
+            position => {
+                self.position = Some(position - 1);
+                Some(&self.entries[position - 1])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_cursor() {
+        let entries = vec!["upstream".to_string(), "origin".to_string()];
+        let mut cursor = HistoryCursor::new(&entries);
+        assert_eq!(cursor.newer(), None);
+        assert_eq!(cursor.older("fork"), Some("upstream"));
+        assert_eq!(cursor.older("upstream"), Some("origin"));
+        assert_eq!(cursor.older("origin"), None);
+        assert_eq!(cursor.newer(), Some("upstream"));
+        // Stepping past the newest entry restores what was typed
+        assert_eq!(cursor.newer(), Some("fork"));
+        assert_eq!(cursor.newer(), None);
+    }
+}
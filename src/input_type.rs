@@ -0,0 +1,458 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The input types that can be given as `input`, with the fields each one
+/// accepts besides `type` when written as a mapping. `Number` is kept as an
+/// alias of `Integer`.
+pub const INPUT_TYPES: &[(&str, &[&str])] = &[
+    ("Text", &["pattern", "message"]),
+    ("Number", &["min", "max"]),
+    ("Integer", &["min", "max"]),
+    ("Float", &[]),
+    ("Path", &[]),
+    ("Enum", &["values"]),
+    ("Secret", &["env"]),
+];
+
+/// The kind of value a node asks for, and how it is checked before it is
+/// inserted into the command
+#[derive(Debug, Clone)]
+pub enum InputType {
+    /// Any text, optionally required to match `pattern` as a whole
+    Text {
+        pattern: Option<Regex>,
+        /// Shown instead of the default message when `pattern` does not match
+        message: Option<String>,
+    },
+    /// A whole number within the optional bounds, both inclusive
+    Integer {
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    Float,
+    /// A filesystem path, completed with Tab relative to the working directory
+    Path,
+    /// One of a fixed set of values, completed with Tab
+    Enum {
+        values: Vec<String>,
+    },
+    /// Any text, masked while typed and never kept in the input history
+    Secret {
+        /// Environment variable the value is exported as. The command then
+        /// refers to the variable instead of containing the value.
+        env: Option<String>,
+    },
+}
+
+impl InputType {
+    /// Whether `c` may be typed after `current`. Characters that can never
+    /// form a valid value are ignored while typing.
+    pub fn accepts_char(&self, current: &str, c: char) -> bool {
+        match self {
+            // Only allow digits and minus sign (at start)
+            InputType::Integer { .. } => c.is_ascii_digit() || (c == '-' && current.is_empty()),
+            InputType::Float => c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'),
+            _ => true,
+        }
+    }
+
+    /// Whether an empty value cancels the input rather than being entered
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, InputType::Integer { .. } | InputType::Float)
+    }
+
+    pub fn is_secret(&self) -> bool {
+        matches!(self, InputType::Secret { .. })
+    }
+
+    /// Checks an entered value, returning the message to show when it is not
+    /// valid
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self {
+            InputType::Text { pattern, message } => match pattern {
+                Some(pattern) if !pattern.is_match(value) => Err(message
+                    .clone()
+                    .unwrap_or_else(|| format!("`{}` does not match the expected format", value))),
+                _ => Ok(()),
+            },
+            InputType::Integer { min, max } => {
+                let number: i64 = value
+                    .parse()
+                    .map_err(|_| format!("`{}` is not a valid number", value))?;
+                if let Some(min) = min.filter(|&min| number < min) {
+                    return Err(format!("must be at least {}", min));
+                }
+                if let Some(max) = max.filter(|&max| number > max) {
+                    return Err(format!("must be at most {}", max));
+                }
+                Ok(())
+            }
+            InputType::Float => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(()),
+                _ => Err(format!("`{}` is not a valid number", value)),
+            },
+            InputType::Path | InputType::Secret { .. } => Ok(()),
+            InputType::Enum { values } => {
+                if values.iter().any(|v| v == value) {
+                    Ok(())
+                } else {
+                    Err(format!("must be one of {}", values.join(", ")))
+                }
+            }
+        }
+    }
+
+    /// Returns the values that `current` can be completed to, sorted
+    pub fn completions(&self, current: &str) -> Vec<String> {
+        match self {
+            InputType::Path => match std::env::current_dir() {
+                Ok(cwd) => complete_path(current, &cwd),
+                Err(_) => vec![],
+            },
+            InputType::Enum { values } => {
+                let mut matches: Vec<String> = values
+                    .iter()
+                    .filter(|v| v.starts_with(current))
+                    .cloned()
+                    .collect();
+                matches.sort();
+                matches
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Builds an input type from its mapping form, checking that the fields
+    /// given apply to the type
+    fn from_spec(spec: InputSpec) -> Result<InputType, String> {
+        let allowed = INPUT_TYPES
+            .iter()
+            .find(|(name, _)| *name == spec.r#type)
+            .map(|(_, fields)| *fields)
+            .ok_or_else(|| format!("unknown input type `{}`", spec.r#type))?;
+        for (field, present) in [
+            ("pattern", spec.pattern.is_some()),
+            ("message", spec.message.is_some()),
+            ("min", spec.min.is_some()),
+            ("max", spec.max.is_some()),
+            ("values", spec.values.is_some()),
+            ("env", spec.env.is_some()),
+        ] {
+            if present && !allowed.contains(&field) {
+                return Err(format!(
+                    "`{}` does not apply to `{}` input",
+                    field, spec.r#type
+                ));
+            }
+        }
+
+        Ok(match spec.r#type.as_str() {
+            "Text" => InputType::Text {
+                pattern: spec
+                    .pattern
+                    .map(|pattern| compile_pattern(&pattern))
+                    .transpose()?,
+                message: spec.message,
+            },
+            "Number" | "Integer" => {
+                if let (Some(min), Some(max)) = (spec.min, spec.max) {
+                    if min > max {
+                        return Err(format!("`min` {} is greater than `max` {}", min, max));
+                    }
+                }
+                InputType::Integer {
+                    min: spec.min,
+                    max: spec.max,
+                }
+            }
+            "Float" => InputType::Float,
+            "Path" => InputType::Path,
+            "Enum" => {
+                let values = spec.values.unwrap_or_default();
+                if values.is_empty() {
+                    return Err("`Enum` input needs at least one of `values`".to_string());
+                }
+                InputType::Enum { values }
+            }
+            "Secret" => {
+                if let Some(env) = spec.env.as_deref().filter(|env| !is_env_name(env)) {
+                    return Err(format!(
+                        "`{}` is not a valid environment variable name",
+                        env
+                    ));
+                }
+                InputType::Secret { env: spec.env }
+            }
+            _ => unreachable!("input types are checked against INPUT_TYPES"),
+        })
+    }
+}
+
+/// Compiles a `pattern`, which must match the whole value
+pub fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| {
+        // The first lines of the error repeat the pattern with a marker
+        let reason = e.to_string();
+        let reason = reason.lines().last().unwrap_or_default().trim().to_string();
+        format!("invalid pattern `{}`: {}", pattern, reason)
+    })
+}
+
+/// Whether `name` can be used as a shell variable name
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// An input type written as a mapping, such as `{ type: Integer, min: 1 }`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InputSpec {
+    r#type: String,
+    pattern: Option<String>,
+    message: Option<String>,
+    min: Option<i64>,
+    max: Option<i64>,
+    values: Option<Vec<String>>,
+    env: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for InputType {
+    fn deserialize<D>(deserializer: D) -> Result<InputType, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum InputTypeHelper {
+            Name(String),
+            Spec(InputSpec),
+        }
+
+        let spec = match InputTypeHelper::deserialize(deserializer)? {
+            InputTypeHelper::Name(name) => InputSpec {
+                r#type: name,
+                pattern: None,
+                message: None,
+                min: None,
+                max: None,
+                values: None,
+                env: None,
+            },
+            InputTypeHelper::Spec(spec) => spec,
+        };
+        InputType::from_spec(spec).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Completes `current` to the entries of the directory it points into,
+/// relative to `cwd`. Directories end in `/`, and hidden entries are only
+/// offered once a `.` has been typed.
+pub fn complete_path(current: &str, cwd: &Path) -> Vec<String> {
+    let (dir_part, file_prefix) = match current.rfind('/') {
+        Some(i) => current.split_at(i + 1),
+        None => ("", current),
+    };
+    let Ok(entries) = fs::read_dir(cwd.join(dir_part)) else {
+        return vec![];
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(file_prefix) || (name.starts_with('.') && file_prefix.is_empty()) {
+                return None;
+            }
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir_part, name, suffix))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// The longest prefix shared by all `values`
+pub fn common_prefix(values: &[String]) -> String {
+    let Some((first, rest)) = values.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for value in rest {
+        let length = prefix
+            .char_indices()
+            .zip(value.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, c), _)| i + c.len_utf8());
+        prefix = &prefix[..length];
+    }
+    prefix.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Result<InputType, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    #[test]
+    fn test_deserialize_names() {
+        assert!(matches!(
+            parse("Text").unwrap(),
+            InputType::Text { pattern: None, .. }
+        ));
+        assert!(matches!(
+            parse("Number").unwrap(),
+            InputType::Integer {
+                min: None,
+                max: None
+            }
+        ));
+        assert!(matches!(parse("Path").unwrap(), InputType::Path));
+        assert!(parse("Colour").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_specs() {
+        let input = parse("{ type: Integer, min: 1, max: 65535 }").unwrap();
+        assert!(matches!(
+            input,
+            InputType::Integer {
+                min: Some(1),
+                max: Some(65535)
+            }
+        ));
+
+        let error = parse("{ type: Text, min: 1 }").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`min` does not apply to `Text` input"));
+
+        let error = parse("{ type: Integer, min: 5, max: 1 }").unwrap_err();
+        assert!(error.to_string().contains("greater than"));
+
+        assert!(parse("{ type: Enum }").is_err());
+        assert!(parse("{ type: Text, pattern: '[a-' }").is_err());
+    }
+
+    #[test]
+    fn test_accepts_char() {
+        let integer = parse("Integer").unwrap();
+        assert!(integer.accepts_char("", '-'));
+        assert!(integer.accepts_char("-1", '2'));
+        assert!(!integer.accepts_char("1", '-'));
+        assert!(!integer.accepts_char("1", 'a'));
+        assert!(InputType::Float.accepts_char("1", '.'));
+        assert!(InputType::Path.accepts_char("src", '/'));
+        assert!(parse("Text").unwrap().accepts_char("abc", ' '));
+    }
+
+    #[test]
+    fn test_validate_integer() {
+        let port = parse("{ type: Integer, min: 1, max: 65535 }").unwrap();
+        assert!(port.validate("8080").is_ok());
+        assert_eq!(port.validate("0"), Err("must be at least 1".to_string()));
+        assert_eq!(
+            port.validate("70000"),
+            Err("must be at most 65535".to_string())
+        );
+        assert_eq!(
+            port.validate("-"),
+            Err("`-` is not a valid number".to_string())
+        );
+        assert!(port.validate("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_validate_float() {
+        assert!(InputType::Float.validate("0.5").is_ok());
+        assert!(InputType::Float.validate("-1e3").is_ok());
+        assert!(InputType::Float.validate("1.2.3").is_err());
+        assert!(InputType::Float.validate("inf").is_err());
+    }
+
+    #[test]
+    fn test_validate_text_pattern() {
+        let name = parse(
+            "{ type: Text, pattern: '[a-z0-9-]+', message: 'use lowercase letters, digits and -' }",
+        )
+        .unwrap();
+        assert!(name.validate("web-1").is_ok());
+        // The pattern must match the whole value
+        assert_eq!(
+            name.validate("Web 1"),
+            Err("use lowercase letters, digits and -".to_string())
+        );
+
+        let unnamed = parse("{ type: Text, pattern: '\\d+' }").unwrap();
+        assert_eq!(
+            unnamed.validate("abc"),
+            Err("`abc` does not match the expected format".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_and_complete_enum() {
+        let env = parse("{ type: Enum, values: [staging, prod, preview] }").unwrap();
+        assert!(env.validate("prod").is_ok());
+        assert_eq!(
+            env.validate("dev"),
+            Err("must be one of staging, prod, preview".to_string())
+        );
+        assert_eq!(env.completions("pr"), vec!["preview", "prod"]);
+    }
+
+    #[test]
+    fn test_deserialize_secret() {
+        assert!(matches!(
+            parse("Secret").unwrap(),
+            InputType::Secret { env: None }
+        ));
+        let token = parse("{ type: Secret, env: API_TOKEN }").unwrap();
+        assert!(matches!(token, InputType::Secret { env: Some(ref env) } if env == "API_TOKEN"));
+        assert!(token.is_secret());
+        assert!(token.completions("").is_empty());
+
+        let error = parse("{ type: Secret, env: 1TOKEN }").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`1TOKEN` is not a valid environment variable name"));
+        assert!(parse("{ type: Text, env: TOKEN }").is_err());
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("schema.yml"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        assert_eq!(complete_path("s", dir.path()), vec!["schema.yml", "src/"]);
+        assert_eq!(complete_path("src/m", dir.path()), vec!["src/main.rs"]);
+        assert_eq!(
+            complete_path("", dir.path()),
+            vec!["schema.yml", "src/"],
+            "hidden entries are only offered after a `.`"
+        );
+        assert_eq!(complete_path(".h", dir.path()), vec![".hidden"]);
+        assert!(complete_path("missing/", dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let values = vec!["src/main.rs".to_string(), "src/mod.rs".to_string()];
+        assert_eq!(common_prefix(&values), "src/m");
+        assert_eq!(common_prefix(&["ü1".to_string(), "ü2".to_string()]), "ü");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
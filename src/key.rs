@@ -0,0 +1,290 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::{CHOICE_KEY, INPUT_KEY};
+
+/// The key a node is bound to.
+///
+/// In the configuration a key is either a single character, or a key name in
+/// angle brackets with optional modifiers: `<C-r>` (Ctrl), `<A-x>` (Alt),
+/// `<S-Tab>` (Shift), `<Tab>`, `<F5>`. A literal `<` is written `<lt>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// A key press, with the modifiers held
+    Press {
+        code: KeyCode,
+        modifiers: KeyModifiers,
+    },
+    /// Stands in for the value entered at an input prompt
+    Input,
+    /// Stands in for the value picked from a list of choices
+    Choice,
+}
+
+/// Named keys that can be written in angle brackets, with the name used when
+/// displaying them first
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Tab", KeyCode::Tab),
+    ("Enter", KeyCode::Enter),
+    ("CR", KeyCode::Enter),
+    ("Return", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+    ("Backspace", KeyCode::Backspace),
+    ("BS", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Del", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("lt", KeyCode::Char('<')),
+];
+
+/// Modifiers that take part in matching; others, such as Super, are ignored
+const MATCHED_MODIFIERS: KeyModifiers = KeyModifiers::CONTROL
+    .union(KeyModifiers::ALT)
+    .union(KeyModifiers::SHIFT);
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is implied by the character itself, and Shift-Tab is reported
+        // by terminals as a separate key code
+        let (code, modifiers) = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => (
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c)),
+                modifiers - KeyModifiers::SHIFT,
+            ),
+            KeyCode::BackTab => (KeyCode::Tab, modifiers | KeyModifiers::SHIFT),
+            _ => (code, modifiers),
+        };
+        Key::Press {
+            code,
+            modifiers: modifiers & MATCHED_MODIFIERS,
+        }
+    }
+
+    pub fn char(c: char) -> Self {
+        Key::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    /// Whether the key event is a press of this key
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        *self == Key::from(event)
+    }
+
+    /// Whether the key is a single character without modifiers
+    pub fn is_plain_char(&self) -> bool {
+        matches!(
+            self,
+            Key::Press {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            } if *c != '<' && *c != ' '
+        )
+    }
+}
+
+impl From<&KeyEvent> for Key {
+    fn from(event: &KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => return Err("key must not be empty".to_string()),
+            (Some(c), None) => return Ok(Key::char(c)),
+            _ => {}
+        }
+
+        let invalid = || format!("invalid key `{}`", s);
+        let inner = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(invalid)?;
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = inner;
+        while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty())
+        {
+            modifiers |= match modifier.to_ascii_uppercase().as_str() {
+                "C" => KeyModifiers::CONTROL,
+                "A" | "M" => KeyModifiers::ALT,
+                "S" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+            name = rest;
+        }
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|&(_, code)| code)
+                .or_else(|| parse_function_key(name))
+                .ok_or_else(invalid)?,
+        };
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+/// Parses `F1` to `F24`
+fn parse_function_key(name: &str) -> Option<KeyCode> {
+    let number: u8 = name.strip_prefix(['F', 'f'])?.parse().ok()?;
+    (1..=24).contains(&number).then_some(KeyCode::F(number))
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (code, modifiers) = match self {
+            Key::Input => return write!(f, "{}", INPUT_KEY),
+            Key::Choice => return write!(f, "{}", CHOICE_KEY),
+            Key::Press { code, modifiers } => (code, modifiers),
+        };
+        if self.is_plain_char() {
+            if let KeyCode::Char(c) = code {
+                return write!(f, "{}", c);
+            }
+        }
+
+        write!(f, "<")?;
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if modifiers.contains(modifier) {
+                write!(f, "{}", prefix)?;
+            }
+        }
+        match code {
+            KeyCode::F(n) => write!(f, "F{}", n)?,
+            KeyCode::Char(c) if *c != '<' && *c != ' ' => write!(f, "{}", c)?,
+            _ => {
+                let name = NAMED_KEYS
+                    .iter()
+                    .find(|(_, named)| named == code)
+                    .map_or("?", |(name, _)| name);
+                write!(f, "{}", name)?;
+            }
+        }
+        write!(f, ">")
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D>(deserializer: D) -> Result<Key, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let notation = String::deserialize(deserializer)?;
+        notation.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_single_character() {
+        assert_eq!("g".parse::<Key>().unwrap(), Key::char('g'));
+        assert_eq!("<".parse::<Key>().unwrap(), Key::char('<'));
+        assert_eq!("G".parse::<Key>().unwrap().to_string(), "G");
+    }
+
+    #[test]
+    fn test_parse_bracketed_keys() {
+        assert_eq!(
+            "<C-r>".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            "<a-x>".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('x'), KeyModifiers::ALT)
+        );
+        assert_eq!(
+            "<tab>".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Tab, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            "<F5>".parse::<Key>().unwrap(),
+            Key::new(KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            "<C-->".parse::<Key>().unwrap(),
+            Key::new(KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+        assert_eq!("<lt>".parse::<Key>().unwrap(), Key::char('<'));
+        assert_eq!("<S-a>".parse::<Key>().unwrap(), Key::char('A'));
+    }
+
+    #[test]
+    fn test_parse_invalid_keys() {
+        assert!("".parse::<Key>().is_err());
+        assert!("gs".parse::<Key>().is_err());
+        assert!("<Foo>".parse::<Key>().is_err());
+        assert!("<X-a>".parse::<Key>().is_err());
+        assert!("<F25>".parse::<Key>().is_err());
+        assert!("<C-r".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_display_is_canonical() {
+        for (notation, expected) in [
+            ("g", "g"),
+            ("<c-r>", "<C-r>"),
+            ("<M-x>", "<A-x>"),
+            ("<CR>", "<Enter>"),
+            ("<S-tab>", "<S-Tab>"),
+            ("<f12>", "<F12>"),
+            ("<", "<lt>"),
+            (" ", "<Space>"),
+            ("<C-A-Del>", "<C-A-Delete>"),
+        ] {
+            assert_eq!(notation.parse::<Key>().unwrap().to_string(), expected);
+        }
+        assert_eq!(Key::Input.to_string(), INPUT_KEY);
+        assert_eq!(Key::Choice.to_string(), CHOICE_KEY);
+    }
+
+    #[test]
+    fn test_matches_key_events() {
+        let key: Key = "<C-r>".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Char('r'), KeyModifiers::CONTROL)));
+        assert!(!key.matches(&event(KeyCode::Char('r'), KeyModifiers::NONE)));
+        assert!(!key.matches(&event(
+            KeyCode::Char('r'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        )));
+
+        // Terminals may or may not report Shift along with an uppercase letter
+        let key: Key = "G".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+        assert!(key.matches(&event(KeyCode::Char('G'), KeyModifiers::NONE)));
+        assert!(!key.matches(&event(KeyCode::Char('g'), KeyModifiers::NONE)));
+
+        let key: Key = "<S-Tab>".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::BackTab, KeyModifiers::SHIFT)));
+
+        assert!(!Key::Input.matches(&event(KeyCode::Enter, KeyModifiers::NONE)));
+    }
+}
@@ -0,0 +1,305 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::settings::KeyBinding;
+
+/// A single line of editable text with a cursor, shared by the prompts.
+///
+/// The cursor always sits on a grapheme boundary, so that characters made of
+/// several code points are moved over and deleted as one. Besides the
+/// arrow keys, Home, End and Delete, the usual readline keys are supported:
+///
+/// | Key                      | Action                                |
+/// |--------------------------|---------------------------------------|
+/// | `<C-b>` / `<C-f>`        | Move one character left / right       |
+/// | `<A-b>` / `<A-f>`        | Move one word left / right            |
+/// | `<C-Left>` / `<C-Right>` | Move one word left / right            |
+/// | `<C-a>` / `<C-e>`        | Move to the start / end               |
+/// | `<C-d>`                  | Delete the character under the cursor |
+/// | `<A-d>`                  | Delete the word after the cursor      |
+/// | `<C-u>` / `<C-k>`        | Delete to the start / end             |
+///
+/// Deleting the word before the cursor is bound to the `delete_word` action.
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    text: String,
+    /// Byte offset of the cursor in `text`
+    cursor: usize,
+}
+
+/// What handling a key did to the line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// The key is not an editing key
+    Ignored,
+    /// Only the cursor moved
+    Moved,
+    /// The text changed
+    Changed,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// The text before the cursor
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// The display width of the text before the cursor, for placing the
+    /// terminal cursor
+    pub fn cursor_width(&self) -> usize {
+        console::measure_text_width(self.before_cursor())
+    }
+
+    /// Replaces the text, placing the cursor at its end
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+    }
+
+    /// A copy of the line with every grapheme replaced by `mask`, keeping the
+    /// cursor in place, for displaying secrets
+    pub fn masked(&self, mask: char) -> LineEditor {
+        let before = self.before_cursor().graphemes(true).count();
+        let after = self.text[self.cursor..].graphemes(true).count();
+        let text: String = std::iter::repeat_n(mask, before + after).collect();
+        LineEditor {
+            cursor: before * mask.len_utf8(),
+            text,
+        }
+    }
+
+    /// Inserts `c` at the cursor
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Applies an editing key. Plain characters are left to the caller, which
+    /// decides whether to insert them.
+    pub fn handle(&mut self, event: &KeyEvent, delete_word: &KeyBinding) -> Edit {
+        if delete_word.matches(event) {
+            let start = self.word_start();
+            return self.delete(start, self.cursor);
+        }
+
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = event.modifiers.contains(KeyModifiers::ALT);
+        match event.code {
+            KeyCode::Left if ctrl => self.move_to(self.word_start()),
+            KeyCode::Right if ctrl => self.move_to(self.word_end()),
+            KeyCode::Left => self.move_to(self.prev_boundary().unwrap_or(self.cursor)),
+            KeyCode::Right => self.move_to(self.next_boundary().unwrap_or(self.cursor)),
+            KeyCode::Home => self.move_to(0),
+            KeyCode::End => self.move_to(self.text.len()),
+            KeyCode::Backspace => match self.prev_boundary() {
+                Some(start) => self.delete(start, self.cursor),
+                None => Edit::Moved,
+            },
+            KeyCode::Delete => match self.next_boundary() {
+                Some(end) => self.delete(self.cursor, end),
+                None => Edit::Moved,
+            },
+            KeyCode::Char(c) if ctrl => match c.to_ascii_lowercase() {
+                'a' => self.move_to(0),
+                'e' => self.move_to(self.text.len()),
+                'b' => self.move_to(self.prev_boundary().unwrap_or(self.cursor)),
+                'f' => self.move_to(self.next_boundary().unwrap_or(self.cursor)),
+                'd' => match self.next_boundary() {
+                    Some(end) => self.delete(self.cursor, end),
+                    None => Edit::Moved,
+                },
+                'u' => self.delete(0, self.cursor),
+                'k' => self.delete(self.cursor, self.text.len()),
+                _ => Edit::Ignored,
+            },
+            KeyCode::Char(c) if alt => match c.to_ascii_lowercase() {
+                'b' => self.move_to(self.word_start()),
+                'f' => self.move_to(self.word_end()),
+                'd' => {
+                    let end = self.word_end();
+                    self.delete(self.cursor, end)
+                }
+                _ => Edit::Ignored,
+            },
+            _ => Edit::Ignored,
+        }
+    }
+
+    fn move_to(&mut self, position: usize) -> Edit {
+        self.cursor = position;
+        Edit::Moved
+    }
+
+    fn delete(&mut self, start: usize, end: usize) -> Edit {
+        if start == end {
+            return Edit::Moved;
+        }
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+        Edit::Changed
+    }
+
+    /// The start of the grapheme before the cursor
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    /// The end of the grapheme after the cursor
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map(|g| self.cursor + g.len())
+    }
+
+    /// The start of the word before the cursor, skipping whitespace first
+    fn word_start(&self) -> usize {
+        let before = self.text[..self.cursor].trim_end_matches(char::is_whitespace);
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    /// The end of the word after the cursor, skipping whitespace first
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let word = after.trim_start_matches(char::is_whitespace);
+        let skipped = after.len() - word.len();
+        self.cursor
+            + skipped
+            + word
+                .char_indices()
+                .find(|(_, c)| c.is_whitespace())
+                .map_or(word.len(), |(i, _)| i)
+    }
+}
+
+/// The character typed by a key press, unless Ctrl or Alt is held
+pub fn typed_char(event: &KeyEvent) -> Option<char> {
+    match event.code {
+        KeyCode::Char(c)
+            if !event
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::Key;
+    use crate::settings::Bindings;
+
+    fn press(editor: &mut LineEditor, notation: &str) -> Edit {
+        let Ok(Key::Press { code, modifiers }) = notation.parse() else {
+            panic!("`{}` is not a key press", notation);
+        };
+        editor.handle(
+            &KeyEvent::new(code, modifiers),
+            &Bindings::default().delete_word,
+        )
+    }
+
+    fn line(text: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        editor.set_text(text);
+        editor
+    }
+
+    #[test]
+    fn test_insert_at_cursor() {
+        let mut editor = line("gt");
+        press(&mut editor, "<Left>");
+        editor.insert('i');
+        assert_eq!(editor.text(), "git");
+        assert_eq!(editor.before_cursor(), "gi");
+    }
+
+    #[test]
+    fn test_moves_over_graphemes() {
+        // "e" followed by a combining acute accent is a single grapheme
+        let mut editor = line("cafe\u{301}!");
+        press(&mut editor, "<Left>");
+        press(&mut editor, "<Left>");
+        assert_eq!(editor.before_cursor(), "caf");
+        assert_eq!(press(&mut editor, "<Delete>"), Edit::Changed);
+        assert_eq!(editor.text(), "caf!");
+
+        let mut editor = line("日本");
+        press(&mut editor, "<BS>");
+        assert_eq!(editor.text(), "日");
+        assert_eq!(editor.cursor_width(), 2);
+    }
+
+    #[test]
+    fn test_masked_keeps_cursor_position() {
+        let mut editor = line("pa\u{301}ss");
+        press(&mut editor, "<Left>");
+        let masked = editor.masked('•');
+        assert_eq!(masked.text(), "••••");
+        assert_eq!(masked.cursor_width(), 3);
+    }
+
+    #[test]
+    fn test_home_end_and_kill() {
+        let mut editor = line("git commit --amend");
+        press(&mut editor, "<C-a>");
+        assert_eq!(editor.before_cursor(), "");
+        press(&mut editor, "<A-f>");
+        assert_eq!(editor.before_cursor(), "git");
+        assert_eq!(press(&mut editor, "<C-k>"), Edit::Changed);
+        assert_eq!(editor.text(), "git");
+        press(&mut editor, "<Home>");
+        assert_eq!(press(&mut editor, "<C-u>"), Edit::Moved);
+        press(&mut editor, "<C-e>");
+        assert_eq!(editor.before_cursor(), "git");
+    }
+
+    #[test]
+    fn test_word_movement_and_deletion() {
+        let mut editor = line("git commit -m ");
+        press(&mut editor, "<C-w>");
+        assert_eq!(editor.text(), "git commit ");
+        press(&mut editor, "<C-Left>");
+        assert_eq!(editor.before_cursor(), "git ");
+        press(&mut editor, "<A-b>");
+        assert_eq!(editor.before_cursor(), "");
+        press(&mut editor, "<A-d>");
+        assert_eq!(editor.text(), " commit ");
+        press(&mut editor, "<C-Right>");
+        assert_eq!(editor.before_cursor(), " commit");
+        press(&mut editor, "<C-w>");
+        press(&mut editor, "<C-w>");
+        assert_eq!(editor.text(), " ");
+    }
+
+    #[test]
+    fn test_plain_characters_are_ignored() {
+        let mut editor = line("a");
+        assert_eq!(press(&mut editor, "b"), Edit::Ignored);
+        assert_eq!(press(&mut editor, "<C-z>"), Edit::Ignored);
+        assert_eq!(editor.text(), "a");
+
+        let shifted = KeyEvent::new(KeyCode::Char('B'), KeyModifiers::SHIFT);
+        assert_eq!(typed_char(&shifted), Some('B'));
+        let ctrl = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(typed_char(&ctrl), None);
+    }
+}
@@ -1,26 +1,56 @@
+mod choices;
+mod command_history;
 mod config;
 mod constants;
+mod diagnostic;
 mod error;
 mod fuzzy_select;
+mod history;
 mod input;
+mod input_type;
+mod key;
+mod line_editor;
+mod matcher;
 mod node;
+mod node_id;
 mod options;
 mod path;
+mod quote;
 mod search;
+mod settings;
+mod store;
 mod terminal;
 mod tui;
+mod usage;
+mod validate;
 
 mod commands;
 
 use commands::integration::Shell;
 use constants::DEFAULT_HEIGHT;
 
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// A command builder tool – which-key for the command line
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Path to the configuration file, used without project-local layers
+    /// [default: $XDG_CONFIG_HOME/which-cmd/commands.yml]
+    #[arg(long, global = true, env = "WHICH_CMD_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Don't remember the command or the values entered, which otherwise
+    /// rank search results and fill the histories
+    #[arg(
+        long,
+        global = true,
+        env = "WHICH_CMD_NO_HISTORY",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    no_history: bool,
+
     #[command(subcommand)]
     cmd: Commands,
 }
@@ -47,7 +77,28 @@ configured to recognize this flag."
         height: usize,
     },
     /// Get a previously built command
-    Get,
+    Get {
+        /// Print `export` lines for the secrets of the command instead, to be
+        /// evaluated by the shell before the command runs and unset after it
+        #[clap(long)]
+        env: bool,
+    },
+    /// List the commands built before, or pick one to use again
+    History {
+        /// Pick a command in the TUI, to be printed by `which-cmd get`
+        #[clap(long)]
+        pick: bool,
+        /// Prefix output with flag for immediate execution, if the picked
+        /// command was run immediately when it was built
+        #[clap(long, short)]
+        immediate: bool,
+        /// Draw a border around the TUI using box-drawing characters
+        #[clap(long, short)]
+        border: bool,
+        /// Height of the TUI content area (borders are added automatically if enabled)
+        #[clap(long, default_value_t = DEFAULT_HEIGHT)]
+        height: usize,
+    },
     /// Generate shell integration code
     Integration {
         #[arg(value_enum)]
@@ -65,11 +116,30 @@ fn main() {
             immediate,
             border,
             height,
-        } => commands::build_command(immediate, border, height),
-        Commands::Get => commands::get_command(),
+        } => commands::build_command(
+            immediate,
+            border,
+            height,
+            !args.no_history,
+            args.config.as_deref(),
+        ),
+        Commands::Get { env } => commands::get_command(env),
+        Commands::History {
+            pick,
+            immediate,
+            border,
+            height,
+        } => commands::history_command(
+            pick,
+            immediate,
+            border,
+            height,
+            !args.no_history,
+            args.config.as_deref(),
+        ),
         Commands::Integration { shell } => commands::integration_command(shell),
         Commands::Doctor => {
-            commands::doctor_command();
+            commands::doctor_command(args.config.as_deref());
             Ok(())
         }
     };
@@ -0,0 +1,276 @@
+use fuzzy_matcher::clangd::ClangdMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Deserialize;
+
+/// How a query is matched against the items of a list.
+///
+/// All modes use smart case: a query is matched case-insensitively unless it
+/// contains an uppercase letter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Fuzzy matching as in skim, the default
+    #[default]
+    Fuzzy,
+    /// Fuzzy matching as in clangd, which favours matches at word starts
+    Clangd,
+    /// The query must appear in the item as typed
+    Exact,
+    /// The item must start with the query
+    Prefix,
+    /// fzf's extended search syntax. The query is split into terms separated
+    /// by spaces, which must all match. A term is matched fuzzily, unless it
+    /// is written `'exact`, `^prefix`, `suffix$` or `^whole$`, and `!` in
+    /// front of a term requires it not to be found.
+    Extended,
+}
+
+/// The score of the best possible match. Every mode scores matches from 0 up
+/// to this, so that scores can be combined with other rankings the same way
+/// whatever the mode.
+pub const MAX_SCORE: i64 = 100;
+
+/// Matches queries against items according to a `MatchMode`
+pub struct Matcher {
+    mode: MatchMode,
+    skim: SkimMatcherV2,
+    clangd: ClangdMatcher,
+}
+
+impl Matcher {
+    pub fn new(mode: MatchMode) -> Self {
+        Matcher {
+            mode,
+            skim: SkimMatcherV2::default().smart_case(),
+            clangd: ClangdMatcher::default().smart_case(),
+        }
+    }
+
+    /// Matches `query` against `text`, returning the score, from 0 up to
+    /// `MAX_SCORE` for better matches, and the positions of the matched
+    /// characters in `text`, counted in chars
+    pub fn match_item(&self, text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+        match self.mode {
+            MatchMode::Fuzzy => fuzzy_match(&self.skim, text, query),
+            MatchMode::Clangd => fuzzy_match(&self.clangd, text, query),
+            MatchMode::Exact => Term::Exact(query).match_text(text, &self.skim),
+            MatchMode::Prefix => Term::Prefix(query).match_text(text, &self.skim),
+            MatchMode::Extended => self.match_extended(text, query),
+        }
+    }
+
+    /// Matches a query in extended syntax. The score is the average of the
+    /// scores of the terms that must be found.
+    fn match_extended(&self, text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+        let mut score = 0;
+        let mut terms = 0;
+        let mut positions = Vec::new();
+        for word in query.split_whitespace() {
+            let (negated, term) = match word.strip_prefix('!') {
+                Some(rest) => (true, Term::parse(rest, true)),
+                None => (false, Term::parse(word, false)),
+            };
+            // A lone `!` or `'` is not a term yet
+            if term.is_empty() {
+                continue;
+            }
+            match (term.match_text(text, &self.skim), negated) {
+                (Some(_), true) | (None, false) => return None,
+                (Some((term_score, term_positions)), false) => {
+                    score += term_score;
+                    terms += 1;
+                    positions.extend(term_positions);
+                }
+                (None, true) => {}
+            }
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        Some((score / terms.max(1), positions))
+    }
+}
+
+/// Matches `query` fuzzily, scaling the score by that of the query matched
+/// against itself, the best a fuzzy matcher can do
+fn fuzzy_match(matcher: &impl FuzzyMatcher, text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+    let (score, positions) = matcher.fuzzy_indices(text, query)?;
+    let best = matcher.fuzzy_match(query, query).unwrap_or(score).max(1);
+    Some(((MAX_SCORE * score / best).clamp(0, MAX_SCORE), positions))
+}
+
+/// A single term of a query in extended syntax
+enum Term<'q> {
+    Fuzzy(&'q str),
+    Exact(&'q str),
+    Prefix(&'q str),
+    Suffix(&'q str),
+    Whole(&'q str),
+}
+
+impl<'q> Term<'q> {
+    /// Parses a term, after any `!`. Negated terms are matched exactly rather
+    /// than fuzzily, as in fzf.
+    fn parse(word: &'q str, negated: bool) -> Self {
+        if let Some(exact) = word.strip_prefix('\'') {
+            return Term::Exact(exact);
+        }
+        match (word.strip_prefix('^'), word.strip_suffix('$')) {
+            (Some(_), Some(_)) if word.len() > 1 => Term::Whole(&word[1..word.len() - 1]),
+            (Some(prefix), _) => Term::Prefix(prefix),
+            (None, Some(suffix)) => Term::Suffix(suffix),
+            (None, None) if negated => Term::Exact(word),
+            (None, None) => Term::Fuzzy(word),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Term::Fuzzy(s)
+            | Term::Exact(s)
+            | Term::Prefix(s)
+            | Term::Suffix(s)
+            | Term::Whole(s) => s.is_empty(),
+        }
+    }
+
+    fn match_text(&self, text: &str, skim: &SkimMatcherV2) -> Option<(i64, Vec<usize>)> {
+        let (needle, start) = match *self {
+            Term::Fuzzy(query) => return fuzzy_match(skim, text, query),
+            Term::Exact(needle) => {
+                let start = find_chars(text, needle, 0..usize::MAX)?;
+                (needle, start)
+            }
+            Term::Prefix(needle) => (needle, find_chars(text, needle, 0..1)?),
+            Term::Suffix(needle) => {
+                let start = text.chars().count().checked_sub(needle.chars().count())?;
+                (needle, find_chars(text, needle, start..start + 1)?)
+            }
+            Term::Whole(needle) => {
+                if text.chars().count() != needle.chars().count() {
+                    return None;
+                }
+                (needle, find_chars(text, needle, 0..1)?)
+            }
+        };
+        // Earlier matches in shorter items rank first, and the query matching
+        // the whole item scores highest
+        let needle_len = needle.chars().count();
+        let score = MAX_SCORE * needle_len as i64 / (text.chars().count() + start).max(1) as i64;
+        Some((score, (start..start + needle_len).collect()))
+    }
+}
+
+/// Finds `needle` in `text` with smart case, starting at a char position in
+/// `starts`. Returns the char position of the first match.
+fn find_chars(text: &str, needle: &str, starts: std::ops::Range<usize>) -> Option<usize> {
+    let case_sensitive = needle.chars().any(char::is_uppercase);
+    let same =
+        |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+    let text: Vec<char> = text.chars().collect();
+    let needle: Vec<char> = needle.chars().collect();
+    let last_start = text.len().checked_sub(needle.len())?;
+    (starts.start..=last_start.min(starts.end.saturating_sub(1))).find(|&start| {
+        text[start..start + needle.len()]
+            .iter()
+            .zip(&needle)
+            .all(|(&a, &b)| same(a, b))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(mode: MatchMode, text: &str, query: &str) -> Option<Vec<usize>> {
+        Matcher::new(mode)
+            .match_item(text, query)
+            .map(|(_, positions)| positions)
+    }
+
+    #[test]
+    fn test_fuzzy_modes() {
+        assert!(positions(MatchMode::Fuzzy, "git checkout", "gco").is_some());
+        assert!(positions(MatchMode::Clangd, "git checkout", "gco").is_some());
+        assert!(positions(MatchMode::Fuzzy, "git checkout", "xyz").is_none());
+    }
+
+    #[test]
+    fn test_exact_and_prefix() {
+        assert_eq!(
+            positions(MatchMode::Exact, "kubectl get pods", "get"),
+            Some(vec![8, 9, 10])
+        );
+        assert_eq!(positions(MatchMode::Exact, "git checkout", "gco"), None);
+        assert_eq!(
+            positions(MatchMode::Prefix, "kubectl get", "kube"),
+            Some(vec![0, 1, 2, 3])
+        );
+        assert_eq!(positions(MatchMode::Prefix, "kubectl get", "get"), None);
+    }
+
+    #[test]
+    fn test_smart_case() {
+        assert!(positions(MatchMode::Exact, "Makefile", "make").is_some());
+        assert!(positions(MatchMode::Exact, "makefile", "Make").is_none());
+        assert!(positions(MatchMode::Fuzzy, "makefile", "Mk").is_none());
+        assert!(positions(MatchMode::Prefix, "ÜBER", "über").is_some());
+    }
+
+    #[test]
+    fn test_extended_syntax() {
+        let matches = |text, query| positions(MatchMode::Extended, text, query).is_some();
+        assert!(matches("src/main.rs", "^src .rs$"));
+        assert!(!matches("src/main.rs", "^main"));
+        assert!(matches("src/main.rs", "'main"));
+        assert!(!matches("src/main.rs", "'mn"));
+        assert!(matches("src/main.rs", "mn"));
+        assert!(!matches("src/main.rs", "src !main"));
+        assert!(matches("src/lib.rs", "src !main"));
+        assert!(!matches("src/lib.rs", "!.rs$"));
+        assert!(matches("lib.rs", "^lib.rs$"));
+        assert!(!matches("src/lib.rs", "^lib.rs$"));
+        // Incomplete terms are ignored while typing
+        assert!(matches("src/lib.rs", "lib !"));
+    }
+
+    #[test]
+    fn test_extended_positions() {
+        assert_eq!(
+            positions(MatchMode::Extended, "src/main.rs", "^src rs$"),
+            Some(vec![0, 1, 2, 9, 10])
+        );
+    }
+
+    #[test]
+    fn test_exact_ranks_earlier_matches_first() {
+        let matcher = Matcher::new(MatchMode::Exact);
+        let (early, _) = matcher.match_item("pods", "po").unwrap();
+        let (late, _) = matcher.match_item("get pods", "po").unwrap();
+        assert!(early > late);
+    }
+
+    #[test]
+    fn test_scores_share_a_scale_across_modes() {
+        for mode in [
+            MatchMode::Fuzzy,
+            MatchMode::Clangd,
+            MatchMode::Exact,
+            MatchMode::Prefix,
+            MatchMode::Extended,
+        ] {
+            let matcher = Matcher::new(mode);
+            let (whole, _) = matcher.match_item("status", "status").unwrap();
+            let (part, _) = matcher.match_item("stash list", "st").unwrap();
+            assert_eq!(whole, MAX_SCORE, "{:?}", mode);
+            assert!((0..=MAX_SCORE).contains(&part), "{:?}: {}", mode, part);
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let mode: MatchMode = serde_yaml::from_str("extended").unwrap();
+        assert_eq!(mode, MatchMode::Extended);
+        assert!(serde_yaml::from_str::<MatchMode>("regex").is_err());
+    }
+}
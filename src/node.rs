@@ -1,28 +1,223 @@
 use serde::Deserialize;
 use std::rc::Rc;
+use std::time::Duration;
 
-use crate::constants::{CHOICE_KEY, INPUT_KEY};
+use crate::choices::{run_choices_command, Choice};
+use crate::constants::{
+    CHOICES_CMD_TIMEOUT_MS, CHOICE_PLACEHOLDER, INPUT_PLACEHOLDER, SECRET_MASK,
+};
+use crate::input_type::InputType;
+use crate::key::Key;
+use crate::matcher::MatchMode;
+use crate::node_id::NodeId;
+use crate::quote::Quote;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
-    pub id: String,
-    pub key: String,
+    pub id: NodeId,
+    pub key: Key,
     pub name: String,
     pub value: String,
     pub is_immediate: bool,
     pub is_fleeting: bool,
     pub is_anchor: bool,
+    /// Attach the next fragment to this one without a separating space
+    pub is_glued: bool,
     pub is_loop: bool,
     pub is_repeatable: bool,
     pub keys: Vec<Rc<Node>>,
-    pub choices: Vec<String>,
+    pub choices: Vec<Choice>,
+    /// Shell command whose output lines are offered as choices
+    pub choices_cmd: Option<String>,
+    /// How long `choices_cmd` may run, overriding the default
+    pub timeout: Option<Duration>,
+    /// How entered or selected values are quoted when inserted
+    pub quote: Quote,
     pub input_type: Option<InputType>,
+    /// Entered when the input is confirmed while empty
+    pub default: Option<String>,
+    /// Hint shown dimmed in an empty input that has no default
+    pub placeholder: Option<String>,
+    /// Joins the selected values on nodes whose choices can be selected
+    /// several at once, `None` if only one can be selected
+    pub separator: Option<String>,
+    /// How the choices are matched, overriding the `match` setting
+    pub match_mode: Option<MatchMode>,
+    /// The value with the secret masked, on nodes created from a `Secret`
+    /// input. Commands containing such a node are shown masked and are never
+    /// kept in the history.
+    pub masked_value: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub enum InputType {
-    Text,
-    Number,
+/// The fields accepted on a node in the configuration. Must match the fields
+/// of `NodeHelper`.
+pub const NODE_FIELDS: &[&str] = &[
+    "key",
+    "name",
+    "value",
+    "immediate",
+    "fleeting",
+    "anchor",
+    "glue",
+    "loop",
+    "keys",
+    "repeatable",
+    "choices",
+    "choices_cmd",
+    "timeout",
+    "quote",
+    "input",
+    "default",
+    "placeholder",
+    "multi",
+    "separator",
+    "match",
+];
+
+/// Fields of a node of which only one may be given
+pub const EXCLUSIVE_NODE_FIELDS: &[&str] = &["keys", "choices", "choices_cmd", "input"];
+
+/// The fields of a node that depend on each other, as given in a configuration
+/// file. Validation and deserialization both check nodes with
+/// `NodeShape::problems`, so that they agree on what is allowed.
+#[derive(Debug, Default)]
+pub struct NodeShape<'a> {
+    pub value: &'a str,
+    /// `keys` is given and not empty
+    pub keys: bool,
+    /// `choices` is given and not empty
+    pub choices: bool,
+    pub choices_cmd: bool,
+    pub input: bool,
+    /// The input is of type `Secret`
+    pub secret: bool,
+    pub default: bool,
+    pub placeholder: bool,
+    pub multi: bool,
+    pub r#match: bool,
+    pub timeout: Option<u64>,
+    pub separator: bool,
+}
+
+/// A rule a node breaks, with the field that breaks it
+#[derive(Debug, PartialEq, Eq)]
+pub struct NodeProblem {
+    pub field: &'static str,
+    pub message: String,
+    pub help: Option<&'static str>,
+}
+
+impl NodeProblem {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        NodeProblem {
+            field,
+            message: message.into(),
+            help: None,
+        }
+    }
+
+    fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
+    }
+}
+
+impl NodeShape<'_> {
+    /// Every rule the node breaks
+    pub fn problems(&self) -> Vec<NodeProblem> {
+        let mut problems = Vec::new();
+
+        let exclusive = [self.keys, self.choices, self.choices_cmd, self.input];
+        let present: Vec<&str> = EXCLUSIVE_NODE_FIELDS
+            .iter()
+            .zip(exclusive)
+            .filter(|&(_, given)| given)
+            .map(|(&field, _)| field)
+            .collect();
+        if let [first, second, ..] = present[..] {
+            problems.push(
+                NodeProblem::new(
+                    second,
+                    format!("node has both `{}` and `{}`", first, second),
+                )
+                .with_help("a node must have only one of choices, choices_cmd, input, or keys"),
+            );
+        }
+
+        if self.secret && self.default {
+            problems.push(NodeProblem::new(
+                "default",
+                "`Secret` input cannot have a `default`",
+            ));
+        }
+        if !self.input {
+            for (field, given) in [("default", self.default), ("placeholder", self.placeholder)] {
+                if given {
+                    problems.push(NodeProblem::new(
+                        field,
+                        format!("`{}` only applies to nodes with `input`", field),
+                    ));
+                }
+            }
+        }
+
+        let has_choices = self.choices || self.choices_cmd;
+        for (field, given) in [("multi", self.multi), ("match", self.r#match)] {
+            if given && !has_choices {
+                problems.push(NodeProblem::new(
+                    field,
+                    format!(
+                        "`{}` only applies to nodes with `choices` or `choices_cmd`",
+                        field
+                    ),
+                ));
+            }
+        }
+        match self.timeout {
+            Some(_) if !self.choices_cmd => problems.push(NodeProblem::new(
+                "timeout",
+                "`timeout` only applies to nodes with `choices_cmd`",
+            )),
+            Some(0) => problems.push(NodeProblem::new(
+                "timeout",
+                "`timeout` must be at least 1 millisecond",
+            )),
+            _ => {}
+        }
+        if self.separator && !self.multi {
+            problems.push(NodeProblem::new(
+                "separator",
+                "`separator` only applies to nodes with `multi: true`",
+            ));
+        }
+
+        // A placeholder the node has nothing to fill with would otherwise
+        // reach the command as it is
+        for (placeholder, filled, help) in [
+            (
+                INPUT_PLACEHOLDER,
+                self.input,
+                "only nodes with `input` fill `{input}`",
+            ),
+            (
+                CHOICE_PLACEHOLDER,
+                has_choices,
+                "only nodes with `choices` or `choices_cmd` fill `{choice}`",
+            ),
+        ] {
+            if self.value.contains(placeholder) && !filled {
+                problems.push(
+                    NodeProblem::new(
+                        "value",
+                        format!("`{}` is never filled on this node", placeholder),
+                    )
+                    .with_help(help),
+                );
+            }
+        }
+
+        problems
+    }
 }
 
 // Implement custom deserialization for Node
@@ -33,8 +228,9 @@ impl<'de> Deserialize<'de> for Node {
     {
         // Define a helper struct with optional name
         #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
         struct NodeHelper {
-            key: String,
+            key: Key,
             name: Option<String>,
             value: Option<String>,
             #[serde(default)]
@@ -44,14 +240,26 @@ impl<'de> Deserialize<'de> for Node {
             #[serde(default)]
             anchor: bool,
             #[serde(default)]
+            glue: bool,
+            #[serde(default)]
             r#loop: bool,
             #[serde(default)]
             keys: Vec<Node>,
             #[serde(default)]
             repeatable: bool,
             #[serde(default)]
-            choices: Vec<String>,
+            choices: Vec<Choice>,
+            choices_cmd: Option<String>,
+            timeout: Option<u64>,
+            #[serde(default)]
+            quote: Quote,
             input: Option<InputType>,
+            default: Option<String>,
+            placeholder: Option<String>,
+            #[serde(default)]
+            multi: bool,
+            separator: Option<String>,
+            r#match: Option<MatchMode>,
         }
 
         let helper = NodeHelper::deserialize(deserializer)?;
@@ -62,36 +270,64 @@ impl<'de> Deserialize<'de> for Node {
             return Err(serde::de::Error::custom("name must not be empty"));
         }
 
-        if [
-            !helper.choices.is_empty(),
-            helper.input.is_some(),
-            !helper.keys.is_empty(),
-        ]
-        .iter()
-        .filter(|&&x| x)
-        .count()
-            > 1
-        {
+        let shape = NodeShape {
+            value: &value,
+            keys: !helper.keys.is_empty(),
+            choices: !helper.choices.is_empty(),
+            choices_cmd: helper.choices_cmd.is_some(),
+            input: helper.input.is_some(),
+            secret: helper.input.as_ref().is_some_and(InputType::is_secret),
+            default: helper.default.is_some(),
+            placeholder: helper.placeholder.is_some(),
+            multi: helper.multi,
+            r#match: helper.r#match.is_some(),
+            timeout: helper.timeout,
+            separator: helper.separator.is_some(),
+        };
+        if let Some(problem) = shape.problems().into_iter().next() {
             return Err(serde::de::Error::custom(format!(
-                "node must have only one of choices, input, or keys: {}",
-                name
+                "{}: {}",
+                problem.message, name
             )));
         }
 
+        if let (Some(input), Some(default)) = (&helper.input, &helper.default) {
+            input.validate(default).map_err(|message| {
+                serde::de::Error::custom(format!(
+                    "default `{}` is not a valid input: {}",
+                    default, message
+                ))
+            })?;
+        }
+
         Ok(Node {
-            // Initialize id with empty string. This will be set later by traversing the tree.
-            id: "".to_string(),
+            // Initialize id with an empty id. This will be set later by traversing the tree.
+            id: NodeId::default(),
             key: helper.key,
             name,
             value,
             is_immediate: helper.immediate,
-            is_fleeting: helper.fleeting || helper.input.is_some() || !helper.choices.is_empty(),
+            is_fleeting: helper.fleeting
+                || helper.input.is_some()
+                || !helper.choices.is_empty()
+                || helper.choices_cmd.is_some(),
             is_anchor: helper.anchor,
+            is_glued: helper.glue,
             is_loop: helper.r#loop,
             is_repeatable: helper.repeatable,
             keys: helper.keys.into_iter().map(Rc::new).collect(),
             choices: helper.choices,
+            choices_cmd: helper.choices_cmd,
+            timeout: helper.timeout.map(Duration::from_millis),
+            quote: helper.quote,
             input_type: helper.input,
+            default: helper.default,
+            placeholder: helper.placeholder,
+            separator: helper
+                .multi
+                .then(|| helper.separator.unwrap_or_else(|| " ".to_string())),
+            match_mode: helper.r#match,
+            masked_value: None,
         })
     }
 }
@@ -102,56 +338,169 @@ impl Node {
     }
 
     pub fn has_choices(&self) -> bool {
-        !self.choices.is_empty()
+        !self.choices.is_empty() || self.choices_cmd.is_some()
     }
 
-    pub fn set_id_from_parent(&mut self, parent_id: &str) {
-        self.id = Node::id_from_parent(parent_id, &self.key);
+    /// Returns the choices to present for this node, running its
+    /// `choices_cmd` if it has one.
+    pub fn resolve_choices(&self) -> crate::error::Result<Vec<Choice>> {
+        match &self.choices_cmd {
+            Some(command) => run_choices_command(
+                command,
+                self.timeout
+                    .unwrap_or(Duration::from_millis(CHOICES_CMD_TIMEOUT_MS)),
+            ),
+            None => Ok(self.choices.clone()),
+        }
     }
 
-    pub fn id_from_parent(parent_id: &str, key: &str) -> String {
-        if !parent_id.is_empty() {
-            format!("{}{}", parent_id, key)
-        } else {
-            key.to_string()
-        }
+    pub fn is_multi(&self) -> bool {
+        self.separator.is_some()
     }
 
+    /// Whether the value of this node contains an `{input}` placeholder. The
+    /// entered value is substituted into the template, and the filled node
+    /// takes the place of this node in the path.
+    pub fn is_input_template(&self) -> bool {
+        self.value.contains(INPUT_PLACEHOLDER)
+    }
+
+    /// Whether the value of this node contains a `{choice}` placeholder, which
+    /// is filled like `{input}` by the selected choices
+    pub fn is_choice_template(&self) -> bool {
+        self.value.contains(CHOICE_PLACEHOLDER)
+    }
+
+    pub fn set_id_from_parent(&mut self, parent_id: &NodeId) {
+        self.id = parent_id.child(self.key);
+    }
+
+    /// Creates the node for the selected choices. Each value is quoted on its
+    /// own, and the values are joined with the node's separator.
     #[must_use]
-    pub fn with_selection(&self, choice: usize) -> Option<Rc<Node>> {
-        let selection = self.choices.get(choice)?;
+    pub fn with_selections(&self, choices: &[Choice], selected: &[usize]) -> Option<Rc<Node>> {
+        let selections = selected
+            .iter()
+            .map(|&choice| choices.get(choice))
+            .collect::<Option<Vec<&Choice>>>()
+            .filter(|selections| !selections.is_empty())?;
+        let separator = self.separator.as_deref().unwrap_or(" ");
+        let value = selections
+            .iter()
+            .map(|selection| self.quote.apply(&selection.value))
+            .collect::<Vec<_>>()
+            .join(separator);
 
+        if self.is_choice_template() {
+            return Some(self.with_filled_template(CHOICE_PLACEHOLDER, &value, None));
+        }
+
+        let names: Vec<&str> = selections.iter().map(|s| s.name.as_str()).collect();
         Some(Rc::new(Node {
-            id: Node::id_from_parent(&self.id, CHOICE_KEY),
-            key: CHOICE_KEY.to_string(),
-            name: selection.to_string(),
-            value: selection.to_string(),
+            id: self.id.child(Key::Choice),
+            key: Key::Choice,
+            name: names.join(", "),
+            value,
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         }))
     }
 
+    /// Creates the node for a value entered at this node's input. A secret
+    /// exported as an environment variable is inserted as a reference to the
+    /// variable instead, which is quoted unless the value is a template.
     #[must_use]
     pub fn with_input(&self, input: &str) -> Rc<Node> {
+        let is_template = self.is_input_template();
+        let value = match &self.input_type {
+            Some(InputType::Secret { env: Some(env) }) if is_template => format!("${{{}}}", env),
+            Some(InputType::Secret { env: Some(env) }) => format!("\"${{{}}}\"", env),
+            _ => self.quote.apply(input),
+        };
+        // What is shown in place of the value. A reference to a variable is
+        // no secret, but still marks the command as containing one.
+        let mask = match &self.input_type {
+            Some(InputType::Secret { env: Some(_) }) => Some(value.clone()),
+            Some(InputType::Secret { env: None }) => Some(SECRET_MASK.to_string()),
+            _ => None,
+        };
+        if is_template {
+            return self.with_filled_template(INPUT_PLACEHOLDER, &value, mask.as_deref());
+        }
+
+        let name = match &self.input_type {
+            Some(InputType::Secret { .. }) => SECRET_MASK.to_string(),
+            _ => input.to_string(),
+        };
         Rc::new(Node {
-            id: Node::id_from_parent(&self.id, input),
-            key: INPUT_KEY.to_string(),
-            name: input.to_string(),
-            value: input.to_string(),
+            id: self.id.child(Key::Input),
+            key: Key::Input,
+            name,
+            value,
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: mask,
+        })
+    }
+
+    #[must_use]
+    fn with_filled_template(
+        &self,
+        placeholder: &str,
+        value: &str,
+        masked: Option<&str>,
+    ) -> Rc<Node> {
+        Rc::new(Node {
+            id: self.id.clone(),
+            key: self.key,
+            name: self.name.clone(),
+            value: self.value.replace(placeholder, value),
+            is_immediate: self.is_immediate,
+            is_fleeting: false,
+            is_anchor: self.is_anchor,
+            is_glued: self.is_glued,
+            is_loop: false,
+            is_repeatable: self.is_repeatable,
+            keys: vec![],
+            choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
+            input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: masked.map(|mask| self.value.replace(placeholder, mask)),
         })
     }
 }
@@ -162,18 +511,27 @@ mod tests {
 
     fn create_test_node(id: &str, key: &str, name: &str, value: &str) -> Rc<Node> {
         Rc::new(Node {
-            id: id.to_string(),
-            key: key.to_string(),
+            id: id.parse().unwrap(),
+            key: key.parse().unwrap(),
             name: name.to_string(),
             value: value.to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         })
     }
 
@@ -185,20 +543,29 @@ mod tests {
 
     #[test]
     fn test_is_leaf_with_keys() {
-        let child = create_test_node("gs", "s", "status", "status");
+        let child = create_test_node("g s", "s", "status", "status");
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![child],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         assert!(!node.is_leaf());
     }
@@ -206,18 +573,27 @@ mod tests {
     #[test]
     fn test_is_leaf_with_choices() {
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
-            choices: vec!["option1".to_string(), "option2".to_string()],
+            choices: vec!["option1".into(), "option2".into()],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         assert!(!node.is_leaf());
     }
@@ -225,18 +601,30 @@ mod tests {
     #[test]
     fn test_is_leaf_with_input() {
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
-            input_type: Some(InputType::Text),
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
+            input_type: Some(InputType::Text {
+                pattern: None,
+                message: None,
+            }),
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         assert!(!node.is_leaf());
     }
@@ -244,18 +632,27 @@ mod tests {
     #[test]
     fn test_has_choices_true() {
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
-            choices: vec!["option1".to_string()],
+            choices: vec!["option1".into()],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         assert!(node.has_choices());
     }
@@ -266,85 +663,100 @@ mod tests {
         assert!(!node.has_choices());
     }
 
-    #[test]
-    fn test_id_from_parent_with_parent() {
-        let id = Node::id_from_parent("git", "s");
-        assert_eq!(id, "gits");
-    }
-
-    #[test]
-    fn test_id_from_parent_without_parent() {
-        let id = Node::id_from_parent("", "g");
-        assert_eq!(id, "g");
-    }
-
     #[test]
     fn test_set_id_from_parent() {
         let node = Rc::new(Node {
-            id: "".to_string(),
-            key: "s".to_string(),
+            id: NodeId::default(),
+            key: Key::char('s'),
             name: "status".to_string(),
             value: "status".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         // Can't mutate inside Rc, so we'll use Rc::make_mut to get mutable reference
         let mut node_mut = Rc::try_unwrap(node).unwrap();
-        node_mut.set_id_from_parent("git");
-        assert_eq!(node_mut.id, "gits");
+        node_mut.set_id_from_parent(&"g".parse().unwrap());
+        assert_eq!(node_mut.id.to_string(), "g s");
     }
 
     #[test]
     fn test_with_selection_valid_index() {
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
-            choices: vec!["branch".to_string(), "commit".to_string()],
+            choices: vec!["branch".into(), "commit".into()],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
 
-        let selected = node.with_selection(0);
+        let selected = node.with_selections(&node.choices, &[0]);
         assert!(selected.is_some());
 
         let selected_node = selected.unwrap();
-        assert_eq!(selected_node.key, CHOICE_KEY);
+        assert_eq!(selected_node.key, Key::Choice);
         assert_eq!(selected_node.name, "branch");
         assert_eq!(selected_node.value, "branch");
-        assert_eq!(selected_node.id, format!("g{}", CHOICE_KEY));
+        assert_eq!(selected_node.id.to_string(), "g [choice]");
     }
 
     #[test]
     fn test_with_selection_invalid_index() {
         let node = Rc::new(Node {
-            id: "g".to_string(),
-            key: "g".to_string(),
+            id: "g".parse().unwrap(),
+            key: Key::char('g'),
             name: "git".to_string(),
             value: "git".to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
-            choices: vec!["branch".to_string()],
+            choices: vec!["branch".into()],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
 
-        let selected = node.with_selection(5);
+        let selected = node.with_selections(&node.choices, &[5]);
         assert!(selected.is_none());
     }
 
@@ -353,10 +765,10 @@ mod tests {
         let node = create_test_node("g", "g", "git", "git");
         let input_node = node.with_input("my-branch-name");
 
-        assert_eq!(input_node.key, INPUT_KEY);
+        assert_eq!(input_node.key, Key::Input);
         assert_eq!(input_node.name, "my-branch-name");
         assert_eq!(input_node.value, "my-branch-name");
-        assert_eq!(input_node.id, format!("gmy-branch-name"));
+        assert_eq!(input_node.id.to_string(), "g [input]");
     }
 
     #[test]
@@ -396,4 +808,317 @@ fleeting: true
             "Explicitly fleeting nodes should be fleeting"
         );
     }
+
+    #[test]
+    fn test_choices_cmd_is_fleeting_and_has_choices() {
+        let yaml = r#"
+key: c
+value: checkout
+choices_cmd: git branch --format='%(refname:short)'
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        assert!(node.is_fleeting);
+        assert!(node.has_choices());
+        assert!(!node.is_leaf());
+    }
+
+    #[test]
+    fn test_choices_cmd_exclusive_with_choices() {
+        let yaml = r#"
+key: c
+value: checkout
+choices:
+  - main
+choices_cmd: git branch
+"#;
+        assert!(serde_yaml::from_str::<Node>(yaml).is_err());
+    }
+
+    #[test]
+    fn test_choices_cmd_timeout() {
+        let yaml = r#"
+key: p
+value: pods
+choices_cmd: kubectl get pods -o name
+timeout: 15000
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(node.timeout, Some(Duration::from_secs(15)));
+
+        let without_command = "key: p\nvalue: pods\ntimeout: 15000\n";
+        assert!(serde_yaml::from_str::<Node>(without_command).is_err());
+        let zero = "key: p\nvalue: pods\nchoices_cmd: ls\ntimeout: 0\n";
+        assert!(serde_yaml::from_str::<Node>(zero).is_err());
+    }
+
+    #[test]
+    fn test_with_selection_labeled_choice() {
+        let yaml = r#"
+key: x
+value: kubectl
+choices:
+  - name: Production (eu-west)
+    value: --context prod-euw
+  - staging
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        let selected = node.with_selections(&node.choices, &[0]).unwrap();
+        assert_eq!(selected.name, "Production (eu-west)");
+        assert_eq!(selected.value, "--context prod-euw");
+    }
+
+    #[test]
+    fn test_with_input_fills_template() {
+        let yaml = r#"
+key: m
+name: commit with message
+value: commit -m "{input}"
+input: Text
+"#;
+        let mut node: Node = serde_yaml::from_str(yaml).unwrap();
+        node.set_id_from_parent(&"g".parse().unwrap());
+        assert!(node.is_input_template());
+        assert!(!node.is_choice_template());
+
+        let filled = node.with_input("fix typo");
+        assert_eq!(filled.value, r#"commit -m "fix typo""#);
+        assert_eq!(filled.id.to_string(), "g m");
+        assert_eq!(filled.key, Key::char('m'));
+        assert!(!filled.is_fleeting);
+        assert!(filled.is_leaf());
+    }
+
+    #[test]
+    fn test_with_selection_fills_template() {
+        let yaml = r#"
+key: p
+name: pods in namespace
+value: kubectl -n {choice} get pods
+choices:
+  - default
+  - kube-system
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        assert!(node.is_choice_template());
+        assert!(!node.is_input_template());
+        let filled = node.with_selections(&node.choices, &[1]).unwrap();
+        assert_eq!(filled.value, "kubectl -n kube-system get pods");
+        assert_eq!(filled.key, Key::char('p'));
+    }
+
+    #[test]
+    fn test_is_template_false_for_plain_value() {
+        let node = create_test_node("g", "g", "git", "git");
+        assert!(!node.is_input_template());
+        assert!(!node.is_choice_template());
+    }
+
+    #[test]
+    fn test_with_input_applies_quote() {
+        let yaml = r#"
+key: m
+name: commit with message
+value: commit -m {input}
+input: Text
+quote: single
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        let filled = node.with_input("don't expand $HOME");
+        assert_eq!(filled.value, r"commit -m 'don'\''t expand $HOME'");
+    }
+
+    #[test]
+    fn test_with_selection_applies_quote_to_value_only() {
+        let yaml = r#"
+key: f
+value: cat
+quote: auto
+choices:
+  - My Documents/notes.txt
+"#;
+        let node: Node = serde_yaml::from_str(yaml).unwrap();
+        let selected = node.with_selections(&node.choices, &[0]).unwrap();
+        assert_eq!(selected.name, "My Documents/notes.txt");
+        assert_eq!(selected.value, "'My Documents/notes.txt'");
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        let yaml = r#"
+key: h
+value: gh
+reset: true
+"#;
+        let error = serde_yaml::from_str::<Node>(yaml).unwrap_err();
+        assert!(error.to_string().contains("unknown field `reset`"));
+    }
+
+    #[test]
+    fn test_input_default_and_placeholder() {
+        let yaml = r#"
+key: r
+name: remote
+input: Text
+default: origin
+placeholder: remote name
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        assert_eq!(node.default.as_deref(), Some("origin"));
+        assert_eq!(node.placeholder.as_deref(), Some("remote name"));
+
+        let yaml = r#"
+key: n
+name: count
+input: { type: Integer, min: 1 }
+default: "0"
+"#;
+        let error = serde_yaml::from_str::<Node>(yaml).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("default `0` is not a valid input: must be at least 1"));
+
+        let yaml = r#"
+key: l
+value: ls
+default: "-la"
+"#;
+        let error = serde_yaml::from_str::<Node>(yaml).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`default` only applies to nodes with `input`"));
+    }
+
+    #[test]
+    fn test_with_input_secret_exported_as_env() {
+        let yaml = r#"
+key: t
+name: token
+value: curl -H
+input: { type: Secret, env: API_TOKEN }
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        let entered = node.with_input("s3cr3t");
+        assert_eq!(entered.value, "\"${API_TOKEN}\"");
+        assert_eq!(entered.name, SECRET_MASK);
+
+        let yaml = r#"
+key: t
+name: token
+value: 'curl -H "Authorization: Bearer {input}"'
+input: { type: Secret, env: API_TOKEN }
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        assert_eq!(
+            node.with_input("s3cr3t").value,
+            "curl -H \"Authorization: Bearer ${API_TOKEN}\""
+        );
+    }
+
+    #[test]
+    fn test_with_input_secret_without_env_inserts_value() {
+        let yaml = r#"
+key: p
+name: password
+input: Secret
+quote: single
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        let entered = node.with_input("hunter 2");
+        assert_eq!(entered.value, "'hunter 2'");
+        assert_eq!(entered.name, SECRET_MASK);
+        assert_eq!(entered.masked_value.as_deref(), Some(SECRET_MASK));
+
+        let yaml = r#"
+key: p
+name: password
+input: Secret
+default: hunter2
+"#;
+        assert!(serde_yaml::from_str::<Node>(yaml).is_err());
+    }
+
+    #[test]
+    fn test_with_input_secret_is_masked_however_quoted() {
+        let yaml = r#"
+key: p
+name: password
+value: mysql -p{input}
+input: Secret
+quote: double
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        let secret = r#"pa"ss$word`\"#;
+        let entered = node.with_input(secret);
+        // Quoting escapes the secret, so it no longer appears as entered
+        assert!(!entered.value.contains(secret));
+        assert_eq!(
+            entered.masked_value.as_deref(),
+            Some(format!("mysql -p{}", SECRET_MASK).as_str())
+        );
+
+        let path = vec![entered];
+        assert_eq!(
+            crate::path::compose_masked_command(&path),
+            format!("mysql -p{}", SECRET_MASK)
+        );
+    }
+
+    #[test]
+    fn test_with_selections_joins_values() {
+        let yaml = r#"
+key: o
+value: ps -o
+multi: true
+separator: ","
+choices: [pid, user, "%cpu"]
+quote: auto
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        assert!(node.is_multi());
+        let choices = node.choices.clone();
+        let selected = node.with_selections(&choices, &[0, 2]).unwrap();
+        assert_eq!(selected.value, "pid,%cpu");
+        assert_eq!(selected.name, "pid, %cpu");
+        assert!(node.with_selections(&choices, &[]).is_none());
+        assert!(node.with_selections(&choices, &[0, 5]).is_none());
+
+        let yaml = r#"
+key: a
+value: git add {choice}
+multi: true
+choices: [a.rs, my notes.md]
+quote: auto
+"#;
+        let node = serde_yaml::from_str::<Node>(yaml).unwrap();
+        let choices = node.choices.clone();
+        let selected = node.with_selections(&choices, &[1, 0]).unwrap();
+        assert_eq!(selected.value, "git add 'my notes.md' a.rs");
+    }
+
+    #[test]
+    fn test_multi_requires_choices() {
+        let yaml = r#"
+key: l
+value: ls
+multi: true
+"#;
+        let error = serde_yaml::from_str::<Node>(yaml).unwrap_err();
+        assert!(error.to_string().contains("`multi` only applies"));
+
+        let yaml = r#"
+key: o
+value: ps -o
+separator: ","
+choices: [pid]
+"#;
+        let error = serde_yaml::from_str::<Node>(yaml).unwrap_err();
+        assert!(error.to_string().contains("`separator` only applies"));
+    }
+
+    #[test]
+    fn test_node_fields_match_helper() {
+        let mut fields = NODE_FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(fields, crate::validate::struct_fields::<Node>());
+    }
 }
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::{CHOICE_KEY, INPUT_KEY};
+use crate::key::Key;
+
+/// Identifies a node by the keys pressed to reach it from the root.
+///
+/// Ids are serialized as their keys separated by spaces, e.g. `g b <C-r>`,
+/// which is the form used wherever ids are stored. Nodes created from an
+/// input or a choice end in `[input]` or `[choice]` rather than in the value
+/// that was entered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NodeId(Vec<Key>);
+
+impl NodeId {
+    /// The id of the node bound to `key` below the node with this id
+    pub fn child(&self, key: Key) -> NodeId {
+        let mut keys = self.0.clone();
+        keys.push(key);
+        NodeId(keys)
+    }
+
+    /// The keys leading to the node, starting at the root
+    pub fn keys(&self) -> &[Key] {
+        &self.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self.0.iter().map(Key::to_string).collect();
+        write!(f, "{}", keys.join(" "))
+    }
+}
+
+impl FromStr for NodeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace()
+            .map(|key| match key {
+                INPUT_KEY => Ok(Key::Input),
+                CHOICE_KEY => Ok(Key::Choice),
+                _ => key.parse(),
+            })
+            .collect::<Result<_, _>>()
+            .map(NodeId)
+    }
+}
+
+impl Serialize for NodeId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeId {
+    fn deserialize<D>(deserializer: D) -> Result<NodeId, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_appends_key() {
+        let id = NodeId::default().child(Key::char('g'));
+        let child = id.child("<C-r>".parse().unwrap());
+        assert_eq!(child.keys().len(), 2);
+        assert_eq!(child.to_string(), "g <C-r>");
+        assert_eq!(id.to_string(), "g");
+    }
+
+    #[test]
+    fn test_round_trips_through_string() {
+        for id in [
+            "g",
+            "g s",
+            "g <C-r> <lt> <Space> [input]",
+            "g b [choice]",
+            "ü ß",
+        ] {
+            let parsed: NodeId = id.parse().unwrap();
+            assert_eq!(parsed.to_string(), id);
+        }
+    }
+
+    #[test]
+    fn test_special_segments() {
+        let id: NodeId = "g m [input]".parse().unwrap();
+        assert_eq!(id.keys()[2], Key::Input);
+        let id: NodeId = "g [choice]".parse().unwrap();
+        assert_eq!(id.keys()[1], Key::Choice);
+    }
+
+    #[test]
+    fn test_parse_invalid_id() {
+        assert!("g status".parse::<NodeId>().is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let id: NodeId = "g <Tab>".parse().unwrap();
+        let yaml = serde_yaml::to_string(&id).unwrap();
+        assert_eq!(serde_yaml::from_str::<NodeId>(&yaml).unwrap(), id);
+    }
+}
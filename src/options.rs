@@ -5,6 +5,8 @@ pub struct Options {
     pub print_immediate_tag: bool,
     pub border: bool,
     pub height: usize,
+    /// Whether completed commands and entered values are remembered
+    pub record_history: bool,
 }
 
 impl Default for Options {
@@ -13,6 +15,7 @@ impl Default for Options {
             print_immediate_tag: false,
             border: false,
             height: DEFAULT_HEIGHT,
+            record_history: true,
         }
     }
 }
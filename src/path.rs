@@ -12,24 +12,49 @@ pub fn pop_to_first_non_is_fleeting(path: &mut Vec<Rc<Node>>) {
 
 #[must_use]
 pub fn compose_command(path: &[Rc<Node>]) -> String {
+    compose(path, |node| &node.value)
+}
+
+/// Composes the command as shown, with the values of secrets masked
+#[must_use]
+pub fn compose_masked_command(path: &[Rc<Node>]) -> String {
+    compose(path, |node| {
+        node.masked_value.as_deref().unwrap_or(&node.value)
+    })
+}
+
+fn compose(path: &[Rc<Node>], value: impl Fn(&Node) -> &str) -> String {
     // Start building the command from the last anchor point
-    let mut command_parts = Vec::new();
     let mut start_index = 0;
     for (i, node) in path.iter().enumerate() {
         if node.is_anchor {
             start_index = i;
         }
     }
+    // Fragments are separated by a space, unless the previous fragment is
+    // glued to the next one. Empty values don't contribute a fragment.
+    let mut command = String::new();
+    let mut glue_next = false;
     for node in &path[start_index..] {
-        command_parts.push(node.value.as_str());
+        let value = value(node);
+        if value.is_empty() {
+            continue;
+        }
+        if !command.is_empty() && !glue_next {
+            command.push(' ');
+        }
+        command.push_str(value);
+        glue_next = node.is_glued;
     }
-    command_parts.join(" ")
+    command
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::key::Key;
     use crate::node::Node;
+    use crate::quote::Quote;
 
     fn create_test_node(
         id: &str,
@@ -40,18 +65,27 @@ mod tests {
         is_fleeting: bool,
     ) -> Rc<Node> {
         Rc::new(Node {
-            id: id.into(),
-            key: key.into(),
+            id: id.parse().unwrap(),
+            key: key.parse().unwrap(),
             name: name.into(),
             value: value.into(),
             is_immediate: false,
             is_fleeting,
             is_anchor,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         })
     }
 
@@ -93,22 +127,81 @@ mod tests {
     fn test_compose_command_with_empty_values() {
         let node1 = create_test_node("g", "g", "git", "git", false, false);
         let node2 = Rc::new(Node {
-            id: "s".into(),
-            key: "s".into(),
+            id: "s".parse().unwrap(),
+            key: Key::char('s'),
             name: "status".into(),
             value: "".into(), // Empty value
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: vec![],
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         });
         let path = vec![node1, node2];
         let command = compose_command(&path);
-        assert_eq!(command, "git ");
+        assert_eq!(command, "git");
+    }
+
+    #[test]
+    fn test_compose_command_skips_empty_values_between_fragments() {
+        let node1 = create_test_node("g", "g", "git", "git", false, false);
+        let node2 = create_test_node("x", "x", "group", "", false, false);
+        let node3 = create_test_node("s", "s", "status", "status", false, false);
+        let path = vec![node1, node2, node3];
+        assert_eq!(compose_command(&path), "git status");
+    }
+
+    #[test]
+    fn test_compose_command_with_glue() {
+        let date = create_test_node("D", "D", "date", "date", false, false);
+        let from_unix = Rc::new(Node {
+            is_glued: true,
+            ..Rc::unwrap_or_clone(create_test_node(
+                "D f",
+                "f",
+                "from unix",
+                "-d @",
+                false,
+                true,
+            ))
+        });
+        let input = Rc::new(Node {
+            key: Key::Input,
+            ..Rc::unwrap_or_clone(create_test_node(
+                "D f [input]",
+                "i",
+                "1700000000",
+                "1700000000",
+                false,
+                false,
+            ))
+        });
+        let path = vec![date, from_unix, input];
+        assert_eq!(compose_command(&path), "date -d @1700000000");
+    }
+
+    #[test]
+    fn test_compose_command_glue_skips_empty_values() {
+        let flag = Rc::new(Node {
+            is_glued: true,
+            ..Rc::unwrap_or_clone(create_test_node("h", "h", "header", "-H", false, false))
+        });
+        let empty = create_test_node("e", "e", "empty", "", false, false);
+        let value = create_test_node("v", "v", "value", "Accept", false, false);
+        let path = vec![flag, empty, value];
+        assert_eq!(compose_command(&path), "-HAccept");
     }
 
     #[test]
@@ -128,39 +221,39 @@ mod tests {
 
         // Should pop the last node (s) and put it back, so both nodes remain
         assert_eq!(path.len(), 2);
-        assert_eq!(path[1].id, "s");
+        assert_eq!(path[1].id.to_string(), "s");
     }
 
     #[test]
     fn test_pop_to_first_non_is_fleeting_with_fleeting() {
         let node1 = create_test_node("g", "g", "git", "git", false, false);
-        let node2 = create_test_node("choice", "c", "choice", "branch-name", false, true);
+        let node2 = create_test_node("g [choice]", "c", "choice", "branch-name", false, true);
 
         let mut path = vec![Rc::clone(&node1), node2];
         pop_to_first_non_is_fleeting(&mut path);
 
         // Should pop through fleeting nodes and stop at the first non-fleeting
         assert_eq!(path.len(), 1);
-        assert_eq!(path[0].id, "g");
+        assert_eq!(path[0].id.to_string(), "g");
     }
 
     #[test]
     fn test_pop_to_first_non_is_fleeting_multiple_fleeting() {
         let node1 = create_test_node("g", "g", "git", "git", false, false);
-        let node2 = create_test_node("f1", "f1", "fleeting1", "fleeting1", false, true);
-        let node3 = create_test_node("f2", "f2", "fleeting2", "fleeting2", false, true);
+        let node2 = create_test_node("<F1>", "<F1>", "fleeting1", "fleeting1", false, true);
+        let node3 = create_test_node("<F1> <F2>", "<F2>", "fleeting2", "fleeting2", false, true);
 
         let mut path = vec![Rc::clone(&node1), node2, node3];
         pop_to_first_non_is_fleeting(&mut path);
 
         // Should pop through all fleeting nodes
         assert_eq!(path.len(), 1);
-        assert_eq!(path[0].id, "g");
+        assert_eq!(path[0].id.to_string(), "g");
     }
 
     #[test]
     fn test_pop_to_first_non_is_fleeting_all_fleeting() {
-        let node1 = create_test_node("f1", "f1", "fleeting1", "fleeting1", false, true);
+        let node1 = create_test_node("<F1>", "<F1>", "fleeting1", "fleeting1", false, true);
 
         let mut path = vec![node1];
         pop_to_first_non_is_fleeting(&mut path);
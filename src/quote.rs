@@ -0,0 +1,116 @@
+use serde::Deserialize;
+
+/// How an entered or selected value is quoted before it is inserted into the
+/// command. The escaping follows POSIX shell rules, which bash and zsh share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quote {
+    /// Insert the value verbatim
+    #[default]
+    None,
+    /// Wrap the value in single quotes, nothing inside is expanded
+    Single,
+    /// Wrap the value in double quotes, escaping `\`, `"`, `$` and `` ` ``,
+    /// and single-quoting `!`
+    Double,
+    /// Single-quote the value only if it contains characters the shell would
+    /// interpret
+    Auto,
+}
+
+impl Quote {
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Quote::None => value.to_string(),
+            Quote::Single => single_quote(value),
+            Quote::Double => double_quote(value),
+            Quote::Auto => {
+                if !value.is_empty() && value.chars().all(is_shell_safe) {
+                    value.to_string()
+                } else {
+                    single_quote(value)
+                }
+            }
+        }
+    }
+}
+
+fn is_shell_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c)
+}
+
+/// Quotes `value` with single quotes. A single quote inside the value ends the
+/// quoted string, is escaped, and a new quoted string is started.
+pub fn single_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quotes `value` with double quotes, escaping the characters that keep their
+/// special meaning inside them. History expansion still sees a `!` escaped with
+/// a backslash in bash, which also keeps the backslash, so a `!` is put in
+/// single quotes between two double-quoted strings instead.
+pub fn double_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '!' {
+            quoted.push_str(r#""'!'""#);
+            continue;
+        }
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_none() {
+        assert_eq!(Quote::None.apply("fix $HOME bug"), "fix $HOME bug");
+    }
+
+    #[test]
+    fn test_quote_single() {
+        assert_eq!(Quote::Single.apply("fix $HOME bug"), "'fix $HOME bug'");
+        assert_eq!(Quote::Single.apply("don't"), r"'don'\''t'");
+    }
+
+    #[test]
+    fn test_quote_double() {
+        assert_eq!(
+            Quote::Double.apply(r#"say "hi" to $USER `now` \o/"#),
+            r#""say \"hi\" to \$USER \`now\` \\o/""#
+        );
+    }
+
+    #[test]
+    fn test_quote_double_keeps_history_expansion_out() {
+        assert_eq!(Quote::Double.apply("fix it!"), r#""fix it"'!'"""#);
+        assert_eq!(Quote::Double.apply("!!"), r#"""'!'""'!'"""#);
+        assert_eq!(Quote::Auto.apply("fix it!"), "'fix it!'");
+    }
+
+    #[test]
+    fn test_quote_auto_leaves_safe_values() {
+        assert_eq!(Quote::Auto.apply("feature/my-branch"), "feature/my-branch");
+        assert_eq!(Quote::Auto.apply("HEAD~1"), "'HEAD~1'");
+    }
+
+    #[test]
+    fn test_quote_auto_quotes_unsafe_values() {
+        assert_eq!(Quote::Auto.apply("fix typo"), "'fix typo'");
+        assert_eq!(Quote::Auto.apply(""), "''");
+    }
+
+    #[test]
+    fn test_quote_deserialize() {
+        let quote: Quote = serde_yaml::from_str("auto").unwrap();
+        assert_eq!(quote, Quote::Auto);
+    }
+}
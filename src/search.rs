@@ -1,9 +1,35 @@
-use crate::{node::Node, path::compose_command};
+use crate::usage::choice_key;
+use crate::{key::Key, node::Node, node_id::NodeId, path::compose_command};
 use std::rc::Rc;
 
 pub struct SearchNode {
-    pub id: String,
+    pub id: NodeId,
     pub command: String,
+    /// The name of the node or choice, when it differs from its value
+    pub name: Option<String>,
+    /// The choice of the node that the entry selects, if any
+    pub choice: Option<usize>,
+    /// What the use of the node or choice is recorded under
+    pub usage_key: String,
+}
+
+/// The nodes a search covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    /// The nodes below the current one
+    Subtree,
+    /// Every node, wherever the search starts
+    Tree,
+}
+
+impl SearchScope {
+    #[must_use]
+    pub fn toggled(self) -> Self {
+        match self {
+            SearchScope::Subtree => SearchScope::Tree,
+            SearchScope::Tree => SearchScope::Subtree,
+        }
+    }
 }
 
 pub fn format_search_options(nodes: &[SearchNode]) -> Vec<String> {
@@ -20,26 +46,33 @@ pub fn format_search_options(nodes: &[SearchNode]) -> Vec<String> {
 }
 
 pub fn format_single_search_option(node: &SearchNode, command_length: usize) -> String {
-    format!(
-        "{:<length$}   {}",
-        &node.command,
-        node.id
-            .chars()
-            .map(|c| c.to_string())
-            .collect::<Vec<_>>()
-            .join(" > "),
-        length = command_length
-    )
-}
-
-pub fn get_search_options(nodes: &[Rc<Node>]) -> Vec<SearchNode> {
-    nodes
+    let keys = node
+        .id
+        .keys()
         .iter()
-        .flat_map(|node| get_search_options_recursively(&node.keys, &[Rc::clone(node)]))
-        .collect()
+        .map(Key::to_string)
+        .collect::<Vec<_>>()
+        .join(" > ");
+    match &node.name {
+        Some(name) => format!(
+            "{:<length$}   {} · {}",
+            &node.command,
+            keys,
+            name,
+            length = command_length
+        ),
+        None => format!(
+            "{:<length$}   {}",
+            &node.command,
+            keys,
+            length = command_length
+        ),
+    }
 }
 
-pub fn get_search_options_recursively(nodes: &[Rc<Node>], path: &[Rc<Node>]) -> Vec<SearchNode> {
+/// Lists `nodes` and everything below them for searching, along with the
+/// static choices of each. `path` leads to `nodes` and starts every command.
+pub fn get_search_options(nodes: &[Rc<Node>], path: &[Rc<Node>]) -> Vec<SearchNode> {
     nodes
         .iter()
         .flat_map(|node| {
@@ -53,10 +86,15 @@ pub fn get_search_options_recursively(nodes: &[Rc<Node>], path: &[Rc<Node>]) ->
             let mut search_nodes = vec![SearchNode {
                 id: node.id.clone(),
                 command,
+                name: (node.name != node.value).then(|| node.name.clone()),
+                choice: None,
+                usage_key: node.id.to_string(),
             }];
 
+            search_nodes.extend(get_choice_options(node, &new_path));
+
             if !node.keys.is_empty() {
-                search_nodes.extend(get_search_options_recursively(&node.keys, &new_path));
+                search_nodes.extend(get_search_options(&node.keys, &new_path));
             }
 
             search_nodes
@@ -64,32 +102,70 @@ pub fn get_search_options_recursively(nodes: &[Rc<Node>], path: &[Rc<Node>]) ->
         .collect()
 }
 
+/// Lists the static choices of the last node of `path`, each with the
+/// command it completes. Choices from a command are only known once it runs.
+fn get_choice_options(node: &Node, path: &[Rc<Node>]) -> Vec<SearchNode> {
+    node.choices
+        .iter()
+        .enumerate()
+        .filter_map(|(index, choice)| {
+            let selected = node.with_selections(&node.choices, &[index])?;
+            let mut choice_path = path.to_vec();
+            if node.is_choice_template() {
+                choice_path.pop();
+            }
+            choice_path.push(selected);
+            Some(SearchNode {
+                id: node.id.clone(),
+                command: compose_command(&choice_path),
+                name: (choice.name != choice.value).then(|| choice.name.clone()),
+                choice: Some(index),
+                usage_key: choice_key(&node.id, &choice.value),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::choices::Choice;
+    use crate::quote::Quote;
 
     fn create_test_node(id: &str, key: &str, value: &str, children: Vec<Rc<Node>>) -> Rc<Node> {
         Rc::new(Node {
-            id: id.to_string(),
-            key: key.to_string(),
+            id: id.parse().unwrap(),
+            key: key.parse().unwrap(),
             name: value.to_string(),
             value: value.to_string(),
             is_immediate: false,
             is_fleeting: false,
             is_anchor: false,
+            is_glued: false,
             is_loop: false,
             is_repeatable: false,
             keys: children,
             choices: vec![],
+            choices_cmd: None,
+            timeout: None,
+            quote: Quote::None,
             input_type: None,
+            default: None,
+            placeholder: None,
+            separator: None,
+            match_mode: None,
+            masked_value: None,
         })
     }
 
     #[test]
     fn test_format_single_search_option() {
         let search_node = SearchNode {
-            id: "gs".to_string(),
+            id: "g s".parse().unwrap(),
             command: "git status".to_string(),
+            name: None,
+            choice: None,
+            usage_key: String::new(),
         };
 
         let formatted = format_single_search_option(&search_node, 15);
@@ -101,12 +177,18 @@ mod tests {
     fn test_format_search_options_with_padding() {
         let nodes = vec![
             SearchNode {
-                id: "g".to_string(),
+                id: "g".parse().unwrap(),
                 command: "git".to_string(),
+                name: None,
+                choice: None,
+                usage_key: String::new(),
             },
             SearchNode {
-                id: "gs".to_string(),
+                id: "g s".parse().unwrap(),
                 command: "git status".to_string(),
+                name: None,
+                choice: None,
+                usage_key: String::new(),
             },
         ];
 
@@ -129,58 +211,58 @@ mod tests {
     #[test]
     fn test_get_search_options_single_node() {
         let node = create_test_node("g", "g", "git", vec![]);
-        let search_nodes = get_search_options(&[node]);
+        let search_nodes = get_search_options(&node.keys, &[Rc::clone(&node)]);
 
-        // get_search_options only returns children, not the node itself
-        // A node with no children returns an empty list
+        // The subtree of a node doesn't include the node itself
+        // A node with no children has an empty subtree
         assert_eq!(search_nodes.len(), 0);
     }
 
     #[test]
     fn test_get_search_options_nested() {
-        let child = create_test_node("gs", "s", "status", vec![]);
+        let child = create_test_node("g s", "s", "status", vec![]);
         let parent = create_test_node("g", "g", "git", vec![child]);
 
-        let search_nodes = get_search_options(&[parent]);
+        let search_nodes = get_search_options(&parent.keys, &[Rc::clone(&parent)]);
 
         // Should only have the child (not the parent itself)
         assert_eq!(search_nodes.len(), 1);
 
         // Should be the child
-        assert_eq!(search_nodes[0].id, "gs");
+        assert_eq!(search_nodes[0].id.to_string(), "g s");
         assert_eq!(search_nodes[0].command, "git status");
     }
 
     #[test]
     fn test_get_search_options_multiple_children() {
-        let child1 = create_test_node("gs", "s", "status", vec![]);
-        let child2 = create_test_node("gc", "c", "commit", vec![]);
+        let child1 = create_test_node("g s", "s", "status", vec![]);
+        let child2 = create_test_node("g c", "c", "commit", vec![]);
         let parent = create_test_node("g", "g", "git", vec![child1, child2]);
 
-        let search_nodes = get_search_options(&[parent]);
+        let search_nodes = get_search_options(&parent.keys, &[Rc::clone(&parent)]);
 
         // Should have 2 children (not the parent)
         assert_eq!(search_nodes.len(), 2);
 
         // Should include both children
-        let ids: Vec<&str> = search_nodes.iter().map(|n| n.id.as_str()).collect();
-        assert!(ids.contains(&"gs"));
-        assert!(ids.contains(&"gc"));
+        let ids: Vec<String> = search_nodes.iter().map(|n| n.id.to_string()).collect();
+        assert!(ids.contains(&"g s".to_string()));
+        assert!(ids.contains(&"g c".to_string()));
     }
 
     #[test]
     fn test_get_search_options_deeply_nested() {
-        let grandchild = create_test_node("gca", "a", "--amend", vec![]);
-        let child = create_test_node("gc", "c", "commit", vec![grandchild]);
+        let grandchild = create_test_node("g c a", "a", "--amend", vec![]);
+        let child = create_test_node("g c", "c", "commit", vec![grandchild]);
         let parent = create_test_node("g", "g", "git", vec![child]);
 
-        let search_nodes = get_search_options(&[parent]);
+        let search_nodes = get_search_options(&parent.keys, &[Rc::clone(&parent)]);
 
         // Should have child and grandchild (2 total, not the root parent)
         assert_eq!(search_nodes.len(), 2);
 
         // Should include the deeply nested node
-        let deepest = search_nodes.iter().find(|n| n.id == "gca");
+        let deepest = search_nodes.iter().find(|n| n.id.to_string() == "g c a");
         assert!(deepest.is_some());
         assert_eq!(deepest.unwrap().command, "git commit --amend");
     }
@@ -190,9 +272,62 @@ mod tests {
         let child = create_test_node("s", "s", "status", vec![]);
         let parent_node = create_test_node("g", "g", "git", vec![]);
 
-        let search_nodes = get_search_options_recursively(&[child], &[parent_node]);
+        let search_nodes = get_search_options(&[child], &[parent_node]);
 
         assert_eq!(search_nodes.len(), 1);
         assert_eq!(search_nodes[0].command, "git status");
     }
+
+    #[test]
+    fn test_get_search_options_whole_tree() {
+        let grandchild = create_test_node("g c a", "a", "--amend", vec![]);
+        let child = create_test_node("g c", "c", "commit", vec![grandchild]);
+        let parent = create_test_node("g", "g", "git", vec![child]);
+        let other = create_test_node("k", "k", "kubectl", vec![]);
+
+        let search_nodes = get_search_options(&[parent, other], &[]);
+
+        let commands: Vec<&str> = search_nodes.iter().map(|n| n.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            vec!["git", "git commit", "git commit --amend", "kubectl"]
+        );
+    }
+
+    #[test]
+    fn test_get_search_options_indexes_names_and_choices() {
+        let mut node = Rc::try_unwrap(create_test_node("g b", "b", "branch", vec![])).unwrap();
+        node.name = "Switch branch".to_string();
+        node.value = "switch".to_string();
+        node.choices = vec![
+            "main".into(),
+            Choice {
+                name: "Development".to_string(),
+                value: "dev".to_string(),
+            },
+        ];
+        let parent = create_test_node("g", "g", "git", vec![Rc::new(node)]);
+
+        let search_nodes = get_search_options(&parent.keys, &[Rc::clone(&parent)]);
+
+        assert_eq!(search_nodes.len(), 3);
+        assert_eq!(search_nodes[0].name.as_deref(), Some("Switch branch"));
+        assert_eq!(search_nodes[0].choice, None);
+        assert_eq!(search_nodes[1].command, "git switch main");
+        assert_eq!(search_nodes[1].name, None);
+        assert_eq!(search_nodes[2].command, "git switch dev");
+        assert_eq!(search_nodes[2].choice, Some(1));
+        assert_eq!(search_nodes[2].id.to_string(), "g b");
+        assert_eq!(search_nodes[0].usage_key, "g b");
+        assert_eq!(search_nodes[2].usage_key, "g b [choice] dev");
+
+        let formatted = format_search_options(&search_nodes);
+        assert!(formatted[2].ends_with("g > b · Development"));
+    }
+
+    #[test]
+    fn test_search_scope_toggles() {
+        assert_eq!(SearchScope::Subtree.toggled(), SearchScope::Tree);
+        assert_eq!(SearchScope::Tree.toggled(), SearchScope::Subtree);
+    }
 }
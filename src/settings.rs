@@ -0,0 +1,349 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Stylize;
+use serde::Deserialize;
+
+use crate::key::Key;
+use crate::matcher::MatchMode;
+
+/// The fields accepted in the `settings` section. Must match the fields of
+/// `SettingsHelper`.
+pub const SETTINGS_FIELDS: &[&str] = &["bindings", "match"];
+
+/// The actions that can be bound in `settings.bindings`. Must match the
+/// fields of `BindingsHelper`.
+pub const BINDING_FIELDS: &[&str] = &[
+    "close",
+    "back",
+    "execute",
+    "search",
+    "next",
+    "prev",
+    "delete_word",
+    "history",
+    "toggle",
+    "page_up",
+    "page_down",
+    "scope",
+    "repeat_last",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub bindings: Bindings,
+    /// How queries are matched in search and in lists of choices
+    pub match_mode: MatchMode,
+}
+
+/// The keys that trigger each action of the TUI. Nodes take precedence over
+/// bindings, so a node bound to the same key hides the action at its level.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    /// Leave without a command, or cancel a prompt
+    pub close: KeyBinding,
+    /// Go back to the previous node
+    pub back: KeyBinding,
+    /// Print the command built so far, or confirm a prompt
+    pub execute: KeyBinding,
+    /// Search below the current node
+    pub search: KeyBinding,
+    /// Move the selection down in a list, or recall a newer entry in a prompt
+    pub next: KeyBinding,
+    /// Move the selection up in a list, or recall an older entry in a prompt
+    pub prev: KeyBinding,
+    /// Delete the word before the cursor in a prompt
+    pub delete_word: KeyBinding,
+    /// Search the values previously entered in a prompt, or the commands
+    /// built before in the main view
+    pub history: KeyBinding,
+    /// Select or deselect an item in a list that allows several
+    pub toggle: KeyBinding,
+    /// Move the selection up by a page in a list
+    pub page_up: KeyBinding,
+    /// Move the selection down by a page in a list
+    pub page_down: KeyBinding,
+    /// Switch a search between the current node and the whole tree
+    pub scope: KeyBinding,
+    /// Build the last command again
+    pub repeat_last: KeyBinding,
+}
+
+/// One or more keys bound to an action
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyBinding(pub Vec<Key>);
+
+impl KeyBinding {
+    fn new(keys: &[Key]) -> Self {
+        KeyBinding(keys.to_vec())
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        self.0.iter().any(|key| key.matches(event))
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.0.contains(key)
+    }
+
+    /// A label for the first key of the binding, as shown in footers
+    pub fn label(&self) -> Option<String> {
+        self.0.first().map(|key| match key {
+            Key::Press {
+                code: KeyCode::Esc,
+                modifiers: KeyModifiers::NONE,
+            } => "󱊷".to_string(),
+            Key::Press {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::NONE,
+            } => "󰁮".to_string(),
+            _ => key.to_string(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyBinding {
+    fn deserialize<D>(deserializer: D) -> Result<KeyBinding, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(Key),
+            Many(Vec<Key>),
+        }
+
+        Ok(match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(key) => KeyBinding(vec![key]),
+            OneOrMany::Many(keys) => KeyBinding(keys),
+        })
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let ctrl = |c| Key::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let plain = |code| Key::new(code, KeyModifiers::NONE);
+        Bindings {
+            close: KeyBinding::new(&[plain(KeyCode::Esc)]),
+            back: KeyBinding::new(&[plain(KeyCode::Backspace)]),
+            execute: KeyBinding::new(&[plain(KeyCode::Enter)]),
+            search: KeyBinding::new(&[Key::char('/')]),
+            next: KeyBinding::new(&[plain(KeyCode::Down), ctrl('n')]),
+            prev: KeyBinding::new(&[plain(KeyCode::Up), ctrl('p')]),
+            delete_word: KeyBinding::new(&[ctrl('w')]),
+            history: KeyBinding::new(&[ctrl('r')]),
+            toggle: KeyBinding::new(&[plain(KeyCode::Tab)]),
+            page_up: KeyBinding::new(&[plain(KeyCode::PageUp)]),
+            page_down: KeyBinding::new(&[plain(KeyCode::PageDown)]),
+            scope: KeyBinding::new(&[ctrl('t')]),
+            repeat_last: KeyBinding::new(&[ctrl('p')]),
+        }
+    }
+}
+
+impl Bindings {
+    /// Help text displayed in the TUI footer
+    pub fn help_text(&self) -> String {
+        Bindings::footer(&[(&self.close, "close"), (&self.back, "back")])
+    }
+
+    /// Help text displayed in the footer of prompts
+    pub fn cancel_text(&self) -> String {
+        Bindings::footer(&[(&self.close, "cancel")])
+    }
+
+    /// Help text displayed in the footer of lists that allow several items
+    pub fn multi_select_text(&self) -> String {
+        Bindings::footer(&[(&self.toggle, "toggle"), (&self.close, "cancel")])
+    }
+
+    /// Help text displayed in the footer of a search that can switch scope
+    pub fn scope_switch_text(&self, action: &str) -> String {
+        Bindings::footer(&[(&self.scope, action), (&self.close, "cancel")])
+    }
+
+    fn footer(entries: &[(&KeyBinding, &str)]) -> String {
+        entries
+            .iter()
+            .filter_map(|(binding, action)| {
+                binding
+                    .label()
+                    .map(|label| format!("{}  {}", label, action.dark_grey()))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// The actions of the main view that are triggered by `key`
+    pub fn actions_for(&self, key: &Key) -> Vec<&'static str> {
+        [
+            (&self.close, "close"),
+            (&self.back, "back"),
+            (&self.execute, "execute"),
+            (&self.search, "search"),
+            (&self.history, "history"),
+            (&self.repeat_last, "repeat_last"),
+        ]
+        .iter()
+        .filter(|(binding, _)| binding.contains(key))
+        .map(|&(_, action)| action)
+        .collect()
+    }
+}
+
+/// Settings as they appear in a configuration file, where every field is
+/// optional and overrides the settings of earlier layers
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsHelper {
+    #[serde(default)]
+    bindings: BindingsHelper,
+    r#match: Option<MatchMode>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingsHelper {
+    close: Option<KeyBinding>,
+    back: Option<KeyBinding>,
+    execute: Option<KeyBinding>,
+    search: Option<KeyBinding>,
+    next: Option<KeyBinding>,
+    prev: Option<KeyBinding>,
+    delete_word: Option<KeyBinding>,
+    history: Option<KeyBinding>,
+    toggle: Option<KeyBinding>,
+    page_up: Option<KeyBinding>,
+    page_down: Option<KeyBinding>,
+    scope: Option<KeyBinding>,
+    repeat_last: Option<KeyBinding>,
+}
+
+impl Settings {
+    /// Overrides the settings that are given in `helper`
+    pub fn apply(&mut self, helper: SettingsHelper) {
+        if let Some(match_mode) = helper.r#match {
+            self.match_mode = match_mode;
+        }
+        let BindingsHelper {
+            close,
+            back,
+            execute,
+            search,
+            next,
+            prev,
+            delete_word,
+            history,
+            toggle,
+            page_up,
+            page_down,
+            scope,
+            repeat_last,
+        } = helper.bindings;
+        let bindings = &mut self.bindings;
+        for (binding, value) in [
+            (&mut bindings.close, close),
+            (&mut bindings.back, back),
+            (&mut bindings.execute, execute),
+            (&mut bindings.search, search),
+            (&mut bindings.next, next),
+            (&mut bindings.prev, prev),
+            (&mut bindings.delete_word, delete_word),
+            (&mut bindings.history, history),
+            (&mut bindings.toggle, toggle),
+            (&mut bindings.page_up, page_up),
+            (&mut bindings.page_down, page_down),
+            (&mut bindings.scope, scope),
+            (&mut bindings.repeat_last, repeat_last),
+        ] {
+            if let Some(value) = value {
+                *binding = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::struct_fields;
+
+    fn parse_settings(yaml: &str) -> Settings {
+        let mut settings = Settings::default();
+        settings.apply(serde_yaml::from_str(yaml).unwrap());
+        settings
+    }
+
+    #[test]
+    fn test_bindings_accept_single_key_or_list() {
+        let settings = parse_settings(
+            r#"
+bindings:
+  close: <C-c>
+  next: [<C-j>, <Down>]
+"#,
+        );
+        let bindings = settings.bindings;
+        assert_eq!(bindings.close.0, vec!["<C-c>".parse().unwrap()]);
+        assert_eq!(bindings.next.0.len(), 2);
+        // Actions that are not given keep their defaults
+        assert_eq!(bindings.search, Bindings::default().search);
+    }
+
+    #[test]
+    fn test_match_mode_setting() {
+        assert_eq!(Settings::default().match_mode, MatchMode::Fuzzy);
+        let settings = parse_settings("match: extended\n");
+        assert_eq!(settings.match_mode, MatchMode::Extended);
+        // Settings that are not given keep their values
+        let mut settings = settings;
+        settings.apply(serde_yaml::from_str("bindings:\n  close: <C-c>\n").unwrap());
+        assert_eq!(settings.match_mode, MatchMode::Extended);
+    }
+
+    #[test]
+    fn test_bindings_reject_unknown_actions() {
+        let result = serde_yaml::from_str::<SettingsHelper>("bindings:\n  quit: q\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_binding_matches_any_of_its_keys() {
+        let bindings = Bindings::default();
+        let ctrl_n = KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        let down = KeyEvent::new(KeyCode::Down, KeyModifiers::NONE);
+        assert!(bindings.next.matches(&ctrl_n));
+        assert!(bindings.next.matches(&down));
+        assert!(!bindings.prev.matches(&down));
+    }
+
+    #[test]
+    fn test_help_text_shows_active_keys() {
+        let default_text = console::strip_ansi_codes(&Bindings::default().help_text()).to_string();
+        assert_eq!(default_text, "󱊷  close  󰁮  back");
+
+        let settings = parse_settings("bindings:\n  close: <C-c>\n  back: []\n");
+        let text = console::strip_ansi_codes(&settings.bindings.help_text()).to_string();
+        assert_eq!(text, "<C-c>  close");
+    }
+
+    #[test]
+    fn test_actions_for_key() {
+        let bindings = Bindings::default();
+        assert_eq!(bindings.actions_for(&Key::char('/')), vec!["search"]);
+        assert!(bindings.actions_for(&Key::char('g')).is_empty());
+        let ctrl_p = Key::new(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(bindings.actions_for(&ctrl_p), vec!["repeat_last"]);
+    }
+
+    #[test]
+    fn test_field_lists_match_helpers() {
+        let mut fields = SETTINGS_FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(fields, struct_fields::<SettingsHelper>());
+
+        let mut fields = BINDING_FIELDS.to_vec();
+        fields.sort_unstable();
+        assert_eq!(fields, struct_fields::<BindingsHelper>());
+    }
+}
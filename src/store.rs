@@ -0,0 +1,110 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::constants::PREFIX;
+use crate::error::Result;
+
+/// Data kept as YAML in a file of the XDG data directory, such as the input
+/// history, the command history and usage
+#[derive(Debug, Default)]
+pub struct YamlStore<T> {
+    /// Where the data is saved, or `None` to keep it in memory only
+    path: Option<PathBuf>,
+    pub data: T,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> YamlStore<T> {
+    /// Opens the store saved as `file_name` in the XDG data directory. If its
+    /// location cannot be determined, the data is kept for this session only.
+    pub fn open(file_name: &str) -> Self {
+        let path = xdg::BaseDirectories::with_prefix(PREFIX)
+            .ok()
+            .and_then(|dirs| dirs.place_data_file(file_name).ok());
+        match path {
+            Some(path) => YamlStore::load(path),
+            None => YamlStore::default(),
+        }
+    }
+
+    /// Loads the data saved at `path`, starting empty if there is no file yet.
+    /// A file that cannot be read or parsed is reported and left as it is,
+    /// keeping the data for this session only rather than replacing the file
+    /// on the next save.
+    pub fn load(path: PathBuf) -> Self {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return YamlStore {
+                    path: Some(path),
+                    data: T::default(),
+                }
+            }
+            Err(e) => return YamlStore::unsaved(&path, &e),
+        };
+        match serde_yaml::from_str(&contents) {
+            Ok(data) => YamlStore {
+                path: Some(path),
+                data,
+            },
+            Err(e) => YamlStore::unsaved(&path, &e),
+        }
+    }
+
+    fn unsaved(path: &Path, error: &dyn std::error::Error) -> Self {
+        eprintln!(
+            "Warning: not saving to {}, which could not be read: {}",
+            path.display(),
+            error
+        );
+        YamlStore::default()
+    }
+
+    /// Writes the data to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        fs::write(path, serde_yaml::to_string(&self.data)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    type Counts = BTreeMap<String, u32>;
+
+    #[test]
+    fn test_missing_file_starts_empty_and_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("counts.yml");
+
+        let mut store: YamlStore<Counts> = YamlStore::load(path.clone());
+        assert!(store.data.is_empty());
+        store.data.insert("g".to_string(), 1);
+        store.save().unwrap();
+
+        let loaded: YamlStore<Counts> = YamlStore::load(path);
+        assert_eq!(loaded.data, store.data);
+    }
+
+    #[test]
+    fn test_invalid_file_is_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("counts.yml");
+        fs::write(&path, "- not: [a map").unwrap();
+
+        let mut store: YamlStore<Counts> = YamlStore::load(path.clone());
+        assert!(store.data.is_empty());
+        store.data.insert("g".to_string(), 1);
+        store.save().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- not: [a map");
+    }
+}
@@ -107,6 +107,13 @@ impl<W: Write> Terminal<W> {
         self.content_rows
     }
 
+    /// Width available to a line written with `write_line`, inside the
+    /// borders or after the leading space
+    pub fn get_content_width(&self) -> usize {
+        let reserved = if self.border { 4 } else { 1 }; // "│ " and " │", or " "
+        (self.terminal_width as usize).saturating_sub(reserved)
+    }
+
     pub fn clear_screen(&mut self) -> Result<()> {
         // Move cursor to start position
         self.writer
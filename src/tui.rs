@@ -1,13 +1,20 @@
+use crate::command_history::{CommandEntry, CommandHistory};
 use crate::config::Config;
-use crate::constants::{help_text, ERROR_DISPLAY_DURATION_MS, IMMEDIATE_PREFIX};
+use crate::constants::{ERROR_DISPLAY_DURATION_MS, IMMEDIATE_PREFIX};
 use crate::error::{Result, WhichCmdError};
-use crate::fuzzy_select::FuzzySelect;
+use crate::fuzzy_select::{FuzzySelect, Outcome};
+use crate::history::InputHistory;
 use crate::input::Input;
+use crate::input_type::InputType;
+use crate::key::Key;
 use crate::node::Node;
+use crate::node_id::NodeId;
 use crate::options::Options;
-use crate::path::{compose_command, pop_to_first_non_is_fleeting};
-use crate::search::{format_search_options, get_search_options};
+use crate::path::{compose_command, compose_masked_command, pop_to_first_non_is_fleeting};
+use crate::search::{format_search_options, get_search_options, SearchScope};
+use crate::settings::{Bindings, Settings};
 use crate::terminal::Terminal;
+use crate::usage::{choice_key, Usage};
 
 use crossterm::{
     event::{self, Event, KeyCode},
@@ -17,13 +24,30 @@ use std::rc::Rc;
 
 /// Rebuilds a path from a node ID by traversing the config tree.
 /// Returns the path and the index of the loop node if one was encountered.
-fn rebuild_path_from_id(node_id: &str, root_keys: &[Rc<Node>]) -> (Vec<Rc<Node>>, Option<usize>) {
+fn rebuild_path_from_id(
+    node_id: &NodeId,
+    root_keys: &[Rc<Node>],
+) -> (Vec<Rc<Node>>, Option<usize>) {
     let mut path: Vec<Rc<Node>> = Vec::new();
+    let loop_node_index = extend_path_to_id(&mut path, node_id, root_keys);
+    (path, loop_node_index)
+}
+
+/// Appends the nodes leading from `nodes`, the siblings offered at the end of
+/// `path`, down to the node with `node_id`. Returns the index of the last loop
+/// node appended, if any.
+fn extend_path_to_id(
+    path: &mut Vec<Rc<Node>>,
+    node_id: &NodeId,
+    nodes: &[Rc<Node>],
+) -> Option<usize> {
     let mut loop_node_index: Option<usize> = None;
-    let mut lookup = root_keys;
+    let mut lookup = nodes;
+    // The keys above `nodes` are already on the path
+    let depth = nodes.first().map_or(0, |n| n.id.keys().len() - 1);
 
-    for part in node_id.split("").filter(|part| !part.is_empty()) {
-        if let Some(node) = lookup.iter().find(|n| n.key == part) {
+    for key in node_id.keys().iter().skip(depth) {
+        if let Some(node) = lookup.iter().find(|n| n.key == *key) {
             path.push(Rc::clone(node));
             // Set loop_node_index if we encounter a loop node
             if node.is_loop {
@@ -33,7 +57,88 @@ fn rebuild_path_from_id(node_id: &str, root_keys: &[Rc<Node>]) -> (Vec<Rc<Node>>
         }
     }
 
-    (path, loop_node_index)
+    loop_node_index
+}
+
+/// Selects a static choice of the last node of `path`, as choosing it from
+/// the list would, and returns the node added for it
+fn select_choice(path: &mut Vec<Rc<Node>>, choice: usize) -> Option<Rc<Node>> {
+    let node = path.last().cloned()?;
+    let selected_node = node.with_selections(&node.choices, &[choice])?;
+    if node.is_choice_template() {
+        path.pop();
+    }
+    path.push(Rc::clone(&selected_node));
+    Some(selected_node)
+}
+
+/// Records the use of the nodes of a completed command, and of the choices
+/// made on the way whose nodes it still contains
+fn record_usage(usage: &mut Usage, path: &[Rc<Node>], chosen: &[(Rc<Node>, String)]) -> Result<()> {
+    let chosen = chosen
+        .iter()
+        .filter(|(node, _)| path.iter().any(|p| Rc::ptr_eq(p, node)))
+        .map(|(_, key)| key.clone());
+    usage.record(path.iter().map(|node| node.id.to_string()).chain(chosen));
+    usage.save()
+}
+
+/// Adds the command built from `path` to the command history. Commands with
+/// secrets are left out, whether the secret is inserted or exported, as
+/// repeating them would need the secret again.
+fn record_command(commands: &mut CommandHistory, path: &[Rc<Node>], immediate: bool) -> Result<()> {
+    if path.iter().any(|node| node.masked_value.is_some()) {
+        return Ok(());
+    }
+    commands.record(CommandEntry::new(
+        compose_command(path),
+        ids(path),
+        immediate,
+    ));
+    commands.save()
+}
+
+/// The ids of the nodes of `path`
+fn ids(path: &[Rc<Node>]) -> Vec<NodeId> {
+    path.iter().map(|node| node.id.clone()).collect()
+}
+
+/// Lets the user pick one of the commands built before
+fn pick_command<W: std::io::Write>(
+    terminal: &mut Terminal<W>,
+    commands: &CommandHistory,
+    settings: &Settings,
+) -> Result<Option<CommandEntry>> {
+    let items: Vec<String> = commands
+        .entries()
+        .iter()
+        .map(|entry| entry.command.clone())
+        .collect();
+    let mut fuzzy_select = FuzzySelect::new(&items)
+        .with_prompt("History:")
+        .with_bindings(&settings.bindings)
+        .with_match_mode(settings.match_mode);
+    Ok(fuzzy_select
+        .interact(terminal)?
+        .map(|index| commands.entries()[index].clone()))
+}
+
+/// Builds a command from the history again, making it the most recent one
+fn repeat_command(
+    commands: &mut CommandHistory,
+    entry: CommandEntry,
+    opts: &Options,
+) -> Result<BuiltCommand> {
+    let immediate = opts.print_immediate_tag && entry.immediate;
+    if opts.record_history {
+        commands.record(CommandEntry::new(
+            entry.command.clone(),
+            entry.path,
+            entry.immediate,
+        ));
+        commands.save()?;
+    }
+    Ok(BuiltCommand::new(entry.command, immediate, &[]))
 }
 
 fn format_node(node: &Node, opts: &Options) -> String {
@@ -76,11 +181,8 @@ fn highlight_command(command: &str) -> String {
 }
 
 fn command_indicator(path: &[Rc<Node>]) -> String {
-    format!(
-        "{} {}",
-        "Command:".grey(),
-        highlight_command(&compose_command(path))
-    )
+    let command = compose_masked_command(path);
+    format!("{} {}", "Command:".grey(), highlight_command(&command))
 }
 
 /// Get the current nodes to display based on path and loop state
@@ -111,20 +213,26 @@ fn get_current_nodes(
     }
 }
 
-/// Sort nodes alphabetically (case-insensitive), with lowercase before uppercase
+/// Sort nodes alphabetically (case-insensitive), with lowercase before uppercase.
+/// Single characters come before keys with modifiers or special keys.
 fn sort_nodes(nodes: &[Rc<Node>]) -> Vec<Rc<Node>> {
     let mut sorted = nodes.to_vec();
     sorted.sort_by(|a, b| {
-        let a_key_lower = a.key.to_lowercase();
-        let b_key_lower = b.key.to_lowercase();
-        match a_key_lower.cmp(&b_key_lower) {
+        let a_key = a.key.to_string();
+        let b_key = b.key.to_string();
+        let a_is_special = !a.key.is_plain_char();
+        let b_is_special = !b.key.is_plain_char();
+        match a_is_special
+            .cmp(&b_is_special)
+            .then_with(|| a_key.to_lowercase().cmp(&b_key.to_lowercase()))
+        {
             std::cmp::Ordering::Equal => {
-                let a_is_lower = a.key.chars().next().unwrap().is_lowercase();
-                let b_is_lower = b.key.chars().next().unwrap().is_lowercase();
+                let a_is_lower = a_key.chars().next().unwrap().is_lowercase();
+                let b_is_lower = b_key.chars().next().unwrap().is_lowercase();
                 match (a_is_lower, b_is_lower) {
                     (true, false) => std::cmp::Ordering::Less,
                     (false, true) => std::cmp::Ordering::Greater,
-                    _ => a.key.cmp(&b.key),
+                    _ => a_key.cmp(&b_key),
                 }
             }
             other => other,
@@ -133,12 +241,52 @@ fn sort_nodes(nodes: &[Rc<Node>]) -> Vec<Rc<Node>> {
     sorted
 }
 
+/// Show an error message alongside the help text on the footer line
+fn show_error<W: std::io::Write>(
+    terminal: &mut Terminal<W>,
+    bindings: &Bindings,
+    message: &str,
+) -> Result<()> {
+    show_status(terminal, bindings, message)?;
+    // Display error for configured duration, or until user presses a key
+    let _ = event::poll(std::time::Duration::from_millis(ERROR_DISPLAY_DURATION_MS));
+    Ok(())
+}
+
+/// Shows `message` in place of the help text until the next key press, which
+/// only dismisses it
+fn show_error_until_key<W: std::io::Write>(
+    terminal: &mut Terminal<W>,
+    bindings: &Bindings,
+    message: &str,
+) -> Result<()> {
+    show_status(terminal, bindings, message)?;
+    loop {
+        let event = event::read()
+            .map_err(|e| WhichCmdError::Terminal(format!("Failed to read event: {}", e)))?;
+        if let Event::Key(_) = event {
+            return Ok(());
+        }
+    }
+}
+
+/// Shows `message` in place of the help text, until the next render
+fn show_status<W: std::io::Write>(
+    terminal: &mut Terminal<W>,
+    bindings: &Bindings,
+    message: &str,
+) -> Result<()> {
+    terminal.replace_last_line(message, &bindings.help_text())?;
+    terminal.flush()
+}
+
 /// Render the main TUI interface
 fn render<W: std::io::Write>(
     terminal: &mut Terminal<W>,
     path: &[Rc<Node>],
     nodes: &[Rc<Node>],
     opts: &Options,
+    bindings: &Bindings,
 ) -> Result<()> {
     terminal.clear_screen()?;
 
@@ -146,7 +294,7 @@ fn render<W: std::io::Write>(
     if !path.is_empty() {
         terminal.write_line(&command_indicator(path))?;
         terminal.empty_border_line()?;
-        let keys_pressed: Vec<&str> = path.iter().map(|node| node.key.as_str()).collect();
+        let keys_pressed: Vec<String> = path.iter().map(|node| node.key.to_string()).collect();
         terminal.write_line(&format!(
             "{} {}",
             "Keys pressed:".grey(),
@@ -203,7 +351,7 @@ fn render<W: std::io::Write>(
 
     // Footer
     terminal.empty_border_line()?;
-    terminal.write_centered(&help_text())?;
+    terminal.write_centered(&bindings.help_text())?;
     terminal.draw_bottom_border()?;
 
     terminal.flush()?;
@@ -211,13 +359,68 @@ fn render<W: std::io::Write>(
     Ok(())
 }
 
-pub fn run_tui(config: Config, opts: Options) -> Result<String> {
+/// The result of a TUI session
+#[derive(Debug, Default)]
+pub struct BuiltCommand {
+    /// The command, with the immediate prefix if requested, or empty if the
+    /// TUI was closed
+    pub command: String,
+    /// Secrets to export before the command runs, as variable name and value
+    pub env: Vec<(String, String)>,
+}
+
+impl BuiltCommand {
+    /// Keeps only the secrets that `command` still refers to, as nodes that
+    /// inserted others may have been removed again
+    fn new(command: String, immediate: bool, secrets: &[(String, String)]) -> Self {
+        let env = secrets
+            .iter()
+            .filter(|(name, _)| command.contains(&format!("${{{}}}", name)))
+            .cloned()
+            .collect();
+        let command = if immediate {
+            format!("{} {}", IMMEDIATE_PREFIX, command)
+        } else {
+            command
+        };
+        BuiltCommand { command, env }
+    }
+}
+
+/// Lets the user pick a command built before, without building a new one
+pub fn run_history_picker(config: Config, opts: Options) -> Result<BuiltCommand> {
+    // Opened before the terminal is set up, so that warnings stay readable
+    let mut commands = CommandHistory::open();
+    let mut terminal = Terminal::new(std::io::stdout(), opts.height);
+
+    terminal.set_border(opts.border);
+    terminal.setup()?;
+
+    let picked = pick_command(&mut terminal, &commands, &config.settings)?;
+    terminal.teardown()?;
+    match picked {
+        Some(entry) => repeat_command(&mut commands, entry, &opts),
+        None => Ok(BuiltCommand::default()),
+    }
+}
+
+pub fn run_tui(config: Config, opts: Options) -> Result<BuiltCommand> {
+    // Opened before the terminal is set up, so that warnings stay readable
+    let mut history = InputHistory::open();
+    let mut usage = Usage::open();
+    let mut commands = CommandHistory::open();
+
     // Initialize terminal
     let mut terminal = Terminal::new(std::io::stdout(), opts.height);
 
     terminal.set_border(opts.border);
     terminal.setup()?;
 
+    let bindings = &config.settings.bindings;
+    // Nodes added by choosing from a list, with what the choice is recorded under
+    let mut chosen: Vec<(Rc<Node>, String)> = Vec::new();
+    // Secrets exported as environment variables
+    let mut exports: Vec<(String, String)> = Vec::new();
     let mut path: Vec<Rc<Node>> = Vec::new();
     let mut loop_node_index: Option<usize> = None;
 
@@ -227,126 +430,237 @@ pub fn run_tui(config: Config, opts: Options) -> Result<String> {
         let sorted_nodes = sort_nodes(&current_nodes);
 
         // Render the TUI
-        render(&mut terminal, &path, &sorted_nodes, &opts)?;
+        render(&mut terminal, &path, &sorted_nodes, &opts, bindings)?;
 
         // Wait for an event
         let key_event = event::read()
             .map_err(|e| WhichCmdError::Terminal(format!("Failed to read event: {}", e)))?;
 
         if let Event::Key(event) = key_event {
-            match event.code {
-                KeyCode::Esc => {
-                    terminal.teardown()?;
-                    return Ok("".into());
+            if let Some(node) = current_nodes.iter().find(|n| n.key.matches(&event)) {
+                path.push(Rc::clone(node));
+                if node.is_loop {
+                    loop_node_index = Some(path.len() - 1);
                 }
-                KeyCode::Char(c) => {
-                    // Handle character input
-                    if let Some(node) = current_nodes.iter().find(|n| n.key == c.to_string()) {
-                        path.push(Rc::clone(node));
-                        if node.is_loop {
-                            loop_node_index = Some(path.len() - 1);
+                if node.is_leaf() {
+                    if loop_node_index.is_none() {
+                        // Build and return the command
+                        let command = compose_command(&path);
+                        terminal.teardown()?;
+                        let immediate = opts.print_immediate_tag && node.is_immediate;
+                        if opts.record_history {
+                            record_usage(&mut usage, &path, &chosen)?;
+                            record_command(&mut commands, &path, immediate)?;
                         }
-                        if node.is_leaf() {
-                            if loop_node_index.is_none() {
-                                // Build and return the command
-                                let command = compose_command(&path);
-                                terminal.teardown()?;
-                                return if opts.print_immediate_tag && node.is_immediate {
-                                    Ok(format!("{} {}", IMMEDIATE_PREFIX, command))
-                                } else {
-                                    Ok(command)
-                                };
+                        return Ok(BuiltCommand::new(command, immediate, &exports));
+                    }
+                } else if node.has_choices() {
+                    if let Some(command) = &node.choices_cmd {
+                        show_status(
+                            &mut terminal,
+                            bindings,
+                            &format!("{} {}…", "Running".grey(), command),
+                        )?;
+                    }
+                    let choices = match node.resolve_choices() {
+                        Ok(choices) => choices,
+                        Err(e) => {
+                            path.pop();
+                            pop_to_first_non_is_fleeting(&mut path);
+                            show_error_until_key(
+                                &mut terminal,
+                                bindings,
+                                &format!("{}", e.to_string().red()),
+                            )?;
+                            continue;
+                        }
+                    };
+                    let labels: Vec<String> = choices.iter().map(|c| c.name.clone()).collect();
+                    let boosts = choices
+                        .iter()
+                        .map(|c| usage.score(&choice_key(&node.id, &c.value)))
+                        .collect();
+                    let mut fuzzy_select = FuzzySelect::new(&labels)
+                        .with_prompt("Choose an option:")
+                        .with_bindings(bindings)
+                        .with_match_mode(node.match_mode.unwrap_or(config.settings.match_mode))
+                        .with_boosts(boosts);
+                    let selection = if node.is_multi() {
+                        fuzzy_select.interact_multi(&mut terminal)?
+                    } else {
+                        fuzzy_select
+                            .interact(&mut terminal)?
+                            .map(|index| vec![index])
+                    };
+                    if let Some(selected) = selection {
+                        if let Some(selected_node) = node.with_selections(&choices, &selected) {
+                            if node.is_choice_template() {
+                                path.pop();
                             }
-                        } else if node.has_choices() {
-                            let mut fuzzy_select =
-                                FuzzySelect::new(&node.choices).with_prompt("Choose an option:");
-                            let selection = fuzzy_select.interact(&mut terminal)?;
-                            if let Some(selection_idx) = selection {
-                                if let Some(selected_node) = node.with_selection(selection_idx) {
-                                    path.push(selected_node);
-                                } else {
-                                    pop_to_first_non_is_fleeting(&mut path);
-                                }
-                            } else {
-                                pop_to_first_non_is_fleeting(&mut path);
+                            for &index in &selected {
+                                let key = choice_key(&node.id, &choices[index].value);
+                                chosen.push((Rc::clone(&selected_node), key));
                             }
-                        } else if let Some(input_type) = &node.input_type {
-                            let input_component = Input::new(input_type, &node.name);
-                            if let Some(input) = input_component.interact(&mut terminal)? {
-                                path.push(node.with_input(&input));
-                            } else {
-                                pop_to_first_non_is_fleeting(&mut path);
+                            path.push(selected_node);
+                        } else {
+                            pop_to_first_non_is_fleeting(&mut path);
+                        }
+                    } else {
+                        pop_to_first_non_is_fleeting(&mut path);
+                    }
+                } else if let Some(input_type) = &node.input_type {
+                    let input_component = Input::new(input_type, &node.name)
+                        .with_bindings(bindings)
+                        .with_match_mode(config.settings.match_mode)
+                        .with_default(node.default.as_deref())
+                        .with_placeholder(node.placeholder.as_deref())
+                        .with_history(history.entries(&node.id));
+                    if let Some(input) = input_component.interact(&mut terminal)? {
+                        match input_type {
+                            InputType::Secret { env: Some(env) } => {
+                                exports.retain(|(name, _)| name != env);
+                                exports.push((env.clone(), input.clone()));
+                            }
+                            InputType::Secret { env: None } => {}
+                            _ if opts.record_history => {
+                                history.record(&node.id, &input);
+                                history.save()?;
                             }
+                            _ => {}
                         }
-                    } else if c == '/' {
-                        // Search
-                        let options = if path.is_empty() {
-                            get_search_options(&config.keys)
-                        } else {
-                            get_search_options(&path)
-                        };
+                        if node.is_input_template() {
+                            path.pop();
+                        }
+                        path.push(node.with_input(&input));
+                    } else {
+                        pop_to_first_non_is_fleeting(&mut path);
+                    }
+                }
+                continue;
+            }
 
-                        let text_options = format_search_options(&options);
-                        let mut fuzzy_select = FuzzySelect::new(&text_options).with_prompt(" :");
-                        if let Some(selection) = fuzzy_select.interact(&mut terminal)? {
+            if bindings.close.matches(&event) {
+                terminal.teardown()?;
+                return Ok(BuiltCommand::default());
+            } else if bindings.search.matches(&event) {
+                // Search below the current node, or the whole tree at the top
+                let mut scope = if path.is_empty() {
+                    SearchScope::Tree
+                } else {
+                    SearchScope::Subtree
+                };
+                let mut query = String::new();
+                loop {
+                    let options = match scope {
+                        SearchScope::Subtree => get_search_options(&current_nodes, &path),
+                        SearchScope::Tree => get_search_options(&config.keys, &[]),
+                    };
+
+                    let text_options = format_search_options(&options);
+                    let boosts = options.iter().map(|o| usage.score(&o.usage_key)).collect();
+                    let mut fuzzy_select = FuzzySelect::new(&text_options)
+                        .with_prompt(" :")
+                        .with_bindings(bindings)
+                        .with_match_mode(config.settings.match_mode)
+                        .with_boosts(boosts)
+                        .with_query(&query);
+                    if !path.is_empty() {
+                        fuzzy_select = fuzzy_select.with_scope_switch(match scope {
+                            SearchScope::Subtree => "search everywhere",
+                            SearchScope::Tree => "search here",
+                        });
+                    }
+                    match fuzzy_select.interact_scoped(&mut terminal)? {
+                        Outcome::Selected(selection) => {
                             let selected_node = &options[selection];
 
-                            // Rebuild path based on the selected node ID
-                            let (new_path, new_loop_index) =
-                                rebuild_path_from_id(&selected_node.id, &config.keys);
-                            path = new_path;
-                            loop_node_index = new_loop_index;
-                        } else {
-                            pop_to_first_non_is_fleeting(&mut path);
+                            match scope {
+                                // Keep what was built so far, including the
+                                // selections made in a loop
+                                SearchScope::Subtree => {
+                                    let new_loop_index = extend_path_to_id(
+                                        &mut path,
+                                        &selected_node.id,
+                                        &current_nodes,
+                                    );
+                                    if new_loop_index.is_some() {
+                                        loop_node_index = new_loop_index;
+                                    }
+                                }
+                                // Rebuild path based on the selected node ID
+                                SearchScope::Tree => {
+                                    let (new_path, new_loop_index) =
+                                        rebuild_path_from_id(&selected_node.id, &config.keys);
+                                    path = new_path;
+                                    loop_node_index = new_loop_index;
+                                }
+                            }
+                            if let Some(choice) = selected_node.choice {
+                                if let Some(node) = select_choice(&mut path, choice) {
+                                    chosen.push((node, selected_node.usage_key.clone()));
+                                }
+                            }
+                        }
+                        Outcome::Cancelled => pop_to_first_non_is_fleeting(&mut path),
+                        Outcome::SwitchedScope(typed) => {
+                            query = typed;
+                            scope = scope.toggled();
+                            continue;
                         }
-                    } else {
-                        // Invalid key pressed - show error alongside help text
-                        terminal.replace_last_line(
-                            &format!("{} {}", "Invalid key:".red(), c),
-                            &help_text(),
-                        )?;
-                        terminal.flush()?;
-                        // Display error for configured duration, or until user presses a key
-                        let _ = event::poll(std::time::Duration::from_millis(
-                            ERROR_DISPLAY_DURATION_MS,
-                        ));
                     }
+                    break;
                 }
-                KeyCode::Backspace => {
-                    if path.pop().is_some() {
-                        pop_to_first_non_is_fleeting(&mut path);
+            } else if bindings.back.matches(&event) {
+                if path.pop().is_some() {
+                    pop_to_first_non_is_fleeting(&mut path);
 
-                        // If loop_node is not contained in path, unset it
-                        if loop_node_index.is_some_and(|l| path.len() <= l) {
-                            loop_node_index = None;
-                        }
+                    // If loop_node is not contained in path, unset it
+                    if loop_node_index.is_some_and(|l| path.len() <= l) {
+                        loop_node_index = None;
                     }
                 }
-                KeyCode::Enter => {
-                    if path.is_empty() {
-                        // Can't execute an empty command - show error alongside help text
-                        terminal.replace_last_line(
-                            &format!("{}", "No command to execute".red()),
-                            &help_text(),
-                        )?;
-                        terminal.flush()?;
-                        // Display error for configured duration, or until user presses a key
-                        let _ = event::poll(std::time::Duration::from_millis(
-                            ERROR_DISPLAY_DURATION_MS,
-                        ));
-                    } else {
-                        let command = compose_command(&path);
-                        terminal.teardown()?;
-                        // Safe to unwrap because we checked is_empty above
-                        let last_node = path.last().unwrap();
-                        return if opts.print_immediate_tag && last_node.is_immediate {
-                            Ok(format!("{} {}", IMMEDIATE_PREFIX, command))
-                        } else {
-                            Ok(command)
-                        };
+            } else if bindings.execute.matches(&event) {
+                if path.is_empty() {
+                    // Can't execute an empty command - show error alongside help text
+                    show_error(
+                        &mut terminal,
+                        bindings,
+                        &format!("{}", "No command to execute".red()),
+                    )?;
+                } else {
+                    let command = compose_command(&path);
+                    terminal.teardown()?;
+                    // Safe to unwrap because we checked is_empty above
+                    let last_node = path.last().unwrap();
+                    let immediate = opts.print_immediate_tag && last_node.is_immediate;
+                    if opts.record_history {
+                        record_usage(&mut usage, &path, &chosen)?;
+                        record_command(&mut commands, &path, immediate)?;
                     }
+                    return Ok(BuiltCommand::new(command, immediate, &exports));
+                }
+            } else if bindings.repeat_last.matches(&event) {
+                if let Some(entry) = commands.entries().first().cloned() {
+                    terminal.teardown()?;
+                    return repeat_command(&mut commands, entry, &opts);
                 }
-                _ => {}
+                show_error(
+                    &mut terminal,
+                    bindings,
+                    &format!("{}", "No command built yet".red()),
+                )?;
+            } else if bindings.history.matches(&event) {
+                if let Some(entry) = pick_command(&mut terminal, &commands, &config.settings)? {
+                    terminal.teardown()?;
+                    return repeat_command(&mut commands, entry, &opts);
+                }
+            } else if let KeyCode::Char(_) | KeyCode::F(_) = event.code {
+                // Invalid key pressed - show error alongside help text
+                show_error(
+                    &mut terminal,
+                    bindings,
+                    &format!("{} {}", "Invalid key:".red(), Key::from(&event)),
+                )?;
             }
         }
     }
@@ -357,6 +671,27 @@ mod tests {
     use super::*;
     use crate::config::Config;
 
+    #[test]
+    fn test_built_command_exports_only_referenced_secrets() {
+        let secrets = vec![
+            ("API_TOKEN".to_string(), "s3cr3t".to_string()),
+            ("UNUSED".to_string(), "other".to_string()),
+        ];
+        let built = BuiltCommand::new(
+            "curl -H \"Authorization: Bearer ${API_TOKEN}\"".to_string(),
+            true,
+            &secrets,
+        );
+        assert_eq!(
+            built.command,
+            format!(
+                "{} curl -H \"Authorization: Bearer ${{API_TOKEN}}\"",
+                IMMEDIATE_PREFIX
+            )
+        );
+        assert_eq!(built.env, vec![secrets[0].clone()]);
+    }
+
     #[test]
     fn test_loop_node_detection_after_search() {
         // Create a config with a loop node structure like the user described:
@@ -380,17 +715,17 @@ keys:
                 name: message
                 value: -m
 "#;
-        let config = Config::from_contents(yaml).unwrap();
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
 
-        // Simulate what happens after search: rebuild path from node ID "gzp"
-        let selected_id = "gzp"; // This is the ID for git stash push
-        let (path, loop_node_index) = rebuild_path_from_id(selected_id, &config.keys);
+        // Simulate what happens after search: rebuild path from node ID "g z p"
+        let selected_id = "g z p".parse().unwrap(); // This is the ID for git stash push
+        let (path, loop_node_index) = rebuild_path_from_id(&selected_id, &config.keys);
 
         // Verify the path was built correctly
         assert_eq!(path.len(), 3);
-        assert_eq!(path[0].key, "g");
-        assert_eq!(path[1].key, "z");
-        assert_eq!(path[2].key, "p");
+        assert_eq!(path[0].key, Key::char('g'));
+        assert_eq!(path[1].key, Key::char('z'));
+        assert_eq!(path[2].key, Key::char('p'));
         assert!(path[2].is_loop, "The 'p' node should have is_loop = true");
 
         // Verify the loop_node_index is set correctly
@@ -404,4 +739,218 @@ keys:
             "loop_node_index should point to the loop node at index 2"
         );
     }
+
+    #[test]
+    fn test_rebuild_path_from_id_with_special_keys() {
+        let yaml = r#"
+keys:
+  - key: <C-g>
+    value: git
+    keys:
+      - key: <
+        value: log
+        keys:
+          - key: <F5>
+            value: --oneline
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let id = "<C-g> <lt> <F5>".parse().unwrap();
+        let (path, loop_node_index) = rebuild_path_from_id(&id, &config.keys);
+
+        assert_eq!(compose_command(&path), "git log --oneline");
+        assert_eq!(loop_node_index, None);
+    }
+
+    #[test]
+    fn test_rebuild_path_from_id_with_non_ascii_keys() {
+        let yaml = r#"
+keys:
+  - key: é
+    value: edit
+    keys:
+      - key: ß
+        name: strict
+        value: --strict
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let id = &config.keys[0].keys[0].id;
+        assert_eq!(id.to_string(), "é ß");
+
+        let (path, _) = rebuild_path_from_id(id, &config.keys);
+        assert_eq!(compose_command(&path), "edit --strict");
+    }
+
+    #[test]
+    fn test_sort_nodes_puts_special_keys_last() {
+        let yaml = r#"
+keys:
+  - key: <Tab>
+    value: tab
+  - key: B
+    value: upper
+  - key: <C-a>
+    value: ctrl
+  - key: b
+    value: lower
+  - key: a
+    value: a
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let keys: Vec<String> = sort_nodes(&config.keys)
+            .iter()
+            .map(|n| n.key.to_string())
+            .collect();
+        assert_eq!(keys, vec!["a", "b", "B", "<C-a>", "<Tab>"]);
+    }
+
+    #[test]
+    fn test_search_selects_choice() {
+        let yaml = r#"
+keys:
+  - key: g
+    value: git
+    keys:
+      - key: s
+        value: switch {choice}
+        choices: [main, dev]
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let options = get_search_options(&config.keys, &[]);
+        let dev = options
+            .iter()
+            .find(|o| o.command == "git switch dev")
+            .unwrap();
+
+        let (mut path, _) = rebuild_path_from_id(&dev.id, &config.keys);
+        select_choice(&mut path, dev.choice.unwrap());
+
+        // The template is replaced by the node with the choice filled in
+        assert_eq!(path.len(), 2);
+        assert_eq!(compose_command(&path), "git switch dev");
+    }
+
+    #[test]
+    fn test_subtree_search_in_loop_keeps_selections() {
+        let yaml = r#"
+keys:
+  - key: l
+    value: ls
+    loop: true
+    keys:
+      - key: l
+        value: -l
+      - key: a
+        value: -a
+      - key: s
+        value: --sort
+        keys:
+          - key: t
+            value: time
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let (mut path, mut loop_node_index) =
+            rebuild_path_from_id(&"l l".parse().unwrap(), &config.keys);
+
+        let current_nodes = get_current_nodes(&config, &path, loop_node_index);
+        let options = get_search_options(&current_nodes, &path);
+        let time = options
+            .iter()
+            .find(|o| o.command == "ls -l --sort time")
+            .unwrap();
+
+        if let Some(index) = extend_path_to_id(&mut path, &time.id, &current_nodes) {
+            loop_node_index = Some(index);
+        }
+        assert_eq!(compose_command(&path), time.command);
+        assert_eq!(loop_node_index, Some(0));
+        // Back in the loop, the options not yet used are offered
+        let current_nodes = get_current_nodes(&config, &path, loop_node_index);
+        assert_eq!(current_nodes.len(), 1);
+        assert_eq!(current_nodes[0].value, "-a");
+    }
+
+    #[test]
+    fn test_record_usage_skips_abandoned_choices() {
+        let yaml = r#"
+keys:
+  - key: g
+    value: git
+    keys:
+      - key: s
+        value: switch {choice}
+        choices: [main, dev]
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let (mut path, _) = rebuild_path_from_id(&"g s".parse().unwrap(), &config.keys);
+        let abandoned = select_choice(&mut path, 0).unwrap();
+        path.pop();
+        let (mut path, _) = rebuild_path_from_id(&"g s".parse().unwrap(), &config.keys);
+        let kept = select_choice(&mut path, 1).unwrap();
+        let chosen = vec![
+            (abandoned, "g s [choice] main".to_string()),
+            (kept, "g s [choice] dev".to_string()),
+        ];
+
+        let mut usage = Usage::default();
+        record_usage(&mut usage, &path, &chosen).unwrap();
+        assert!(usage.score("g s") > 0);
+        assert!(usage.score("g s [choice] dev") > 0);
+        assert_eq!(usage.score("g s [choice] main"), 0);
+    }
+
+    #[test]
+    fn test_record_command_leaves_out_secrets() {
+        let yaml = r#"
+keys:
+  - key: m
+    value: mysql
+    keys:
+      - key: p
+        value: -p{input}
+        input: Secret
+        quote: double
+  - key: c
+    value: curl -u
+    input: { type: Secret, env: API_TOKEN }
+  - key: g
+    value: git status
+"#;
+        let config = Config::from_contents(yaml, std::path::Path::new("")).unwrap();
+        let mut commands = CommandHistory::default();
+
+        // A secret that quoting escapes still keeps the command out
+        let (mut path, _) = rebuild_path_from_id(&"m p".parse().unwrap(), &config.keys);
+        let entered = path.pop().unwrap().with_input("pa\"ss!$`");
+        path.push(entered);
+        record_command(&mut commands, &path, false).unwrap();
+
+        let (mut path, _) = rebuild_path_from_id(&"c".parse().unwrap(), &config.keys);
+        let entered = path.pop().unwrap().with_input("s3cr3t");
+        path.push(entered);
+        record_command(&mut commands, &path, false).unwrap();
+
+        let (path, _) = rebuild_path_from_id(&"g".parse().unwrap(), &config.keys);
+        record_command(&mut commands, &path, false).unwrap();
+
+        assert_eq!(commands.entries().len(), 1);
+        assert_eq!(commands.entries()[0].command, "git status");
+    }
+
+    #[test]
+    fn test_placeholder_of_other_kind_is_rejected() {
+        let yaml = r#"
+keys:
+  - key: k
+    value: kubectl --context {input}
+    choices: [prod, staging]
+"#;
+        // Selecting a choice would leave `{input}` unfilled in the command
+        match Config::from_contents(yaml, std::path::Path::new("")) {
+            Err(WhichCmdError::InvalidConfig(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].location(), "4:12");
+            }
+            other => panic!("Expected InvalidConfig, got {:?}", other.map(|_| ())),
+        }
+    }
 }
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{CHOICE_KEY, MAX_USAGE_ENTRIES, USAGE_FILE_NAME};
+use crate::error::Result;
+use crate::node_id::NodeId;
+use crate::store::YamlStore;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Scales frecency to the scores of the matchers, which range up to
+/// `MAX_SCORE` in every mode, so that a frequently used item beats a slightly
+/// better match but not a much better one
+const FRECENCY_WEIGHT: f64 = 20.0;
+
+/// How often and how recently the nodes and choices of completed commands
+/// were used, for ranking them in search and choice lists.
+///
+/// Usage is stored as YAML in the XDG data directory, keyed by node id, or
+/// by node id and value for choices, e.g.
+///
+/// ```yaml
+/// g c:
+///   count: 12
+///   last_used: 1767225600
+/// g b [choice] main:
+///   count: 3
+///   last_used: 1767222000
+/// ```
+#[derive(Debug, Default)]
+pub struct Usage {
+    store: YamlStore<BTreeMap<String, UsageEntry>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct UsageEntry {
+    count: u32,
+    /// Seconds since the Unix epoch
+    last_used: u64,
+}
+
+impl UsageEntry {
+    /// The number of uses, weighted by how recent the last one was
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            1.0
+        } else {
+            0.5
+        };
+        f64::from(self.count) * weight
+    }
+}
+
+/// The key under which selecting `choice` at the node with `id` is recorded
+pub fn choice_key(id: &NodeId, choice: &str) -> String {
+    format!("{} {} {}", id, CHOICE_KEY, choice)
+}
+
+impl Usage {
+    /// Opens the usage in the XDG data directory
+    pub fn open() -> Self {
+        Usage {
+            store: YamlStore::open(USAGE_FILE_NAME),
+        }
+    }
+
+    /// A boost for the item recorded under `key`, to be added to its match
+    /// score. Items that were never used get none.
+    pub fn score(&self, key: &str) -> i64 {
+        self.score_at(key, now())
+    }
+
+    fn score_at(&self, key: &str, now: u64) -> i64 {
+        self.store.data.get(key).map_or(0, |entry| {
+            (FRECENCY_WEIGHT * entry.frecency(now).ln_1p()).round() as i64
+        })
+    }
+
+    /// Records one use of each of `keys`, however often they appear
+    pub fn record<I: IntoIterator<Item = String>>(&mut self, keys: I) {
+        self.record_at(keys, now());
+    }
+
+    fn record_at<I: IntoIterator<Item = String>>(&mut self, keys: I, now: u64) {
+        for key in keys.into_iter().collect::<BTreeSet<_>>() {
+            let entry = self.store.data.entry(key).or_insert(UsageEntry {
+                count: 0,
+                last_used: now,
+            });
+            entry.count = entry.count.saturating_add(1);
+            entry.last_used = now;
+        }
+        // Forget the least recently used entries
+        let entries = &mut self.store.data;
+        if entries.len() > MAX_USAGE_ENTRIES {
+            let mut last_used: Vec<u64> = entries.values().map(|e| e.last_used).collect();
+            last_used.sort_unstable_by(|a, b| b.cmp(a));
+            let cutoff = last_used[MAX_USAGE_ENTRIES - 1];
+            entries.retain(|_, entry| entry.last_used >= cutoff);
+        }
+    }
+
+    /// Writes usage to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        self.store.save()
+    }
+}
+
+/// The current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn load(path: PathBuf) -> Usage {
+        Usage {
+            store: YamlStore::load(path),
+        }
+    }
+
+    const NOW: u64 = 1_767_225_600;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn test_frequent_and_recent_items_score_higher() {
+        let mut usage = Usage::default();
+        usage.record_at(keys(&["g", "g c"]), NOW - 2 * WEEK);
+        usage.record_at(keys(&["g", "g c"]), NOW - 2 * WEEK);
+        usage.record_at(keys(&["g", "g s"]), NOW - 2 * WEEK);
+        usage.record_at(keys(&["k"]), NOW - 10);
+
+        assert!(usage.score_at("g", NOW) > usage.score_at("g c", NOW));
+        assert!(usage.score_at("g c", NOW) > usage.score_at("g s", NOW));
+        // A single recent use beats a couple of old ones
+        assert!(usage.score_at("k", NOW) > usage.score_at("g c", NOW));
+        assert_eq!(usage.score_at("g p", NOW), 0);
+    }
+
+    #[test]
+    fn test_record_counts_repeated_keys_once() {
+        let mut usage = Usage::default();
+        usage.record_at(keys(&["g a", "g a"]), NOW);
+        assert_eq!(usage.store.data["g a"].count, 1);
+    }
+
+    #[test]
+    fn test_record_forgets_least_recently_used() {
+        let mut usage = Usage::default();
+        for i in 0..MAX_USAGE_ENTRIES as u64 + 3 {
+            usage.record_at([i.to_string()], NOW + i);
+        }
+        assert_eq!(usage.store.data.len(), MAX_USAGE_ENTRIES);
+        assert!(!usage.store.data.contains_key("0"));
+        assert!(usage
+            .store
+            .data
+            .contains_key(&(MAX_USAGE_ENTRIES + 2).to_string()));
+    }
+
+    #[test]
+    fn test_choice_key() {
+        let id: NodeId = "g b".parse().unwrap();
+        assert_eq!(choice_key(&id, "main"), "g b [choice] main");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(USAGE_FILE_NAME);
+
+        let mut usage = load(path.clone());
+        usage.record_at(keys(&["g c"]), NOW);
+        usage.save().unwrap();
+
+        let loaded = load(path);
+        assert_eq!(loaded.store.data, usage.store.data);
+        assert!(loaded.score_at("g c", NOW) > 0);
+    }
+}
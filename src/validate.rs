@@ -0,0 +1,677 @@
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use crate::choices::CHOICE_FIELDS;
+use crate::config::{CONFIG_FIELDS, EXTENSION_FIELDS};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::input_type::{compile_pattern, INPUT_TYPES};
+use crate::key::Key;
+use crate::node::{NodeShape, NODE_FIELDS};
+use crate::settings::{BINDING_FIELDS, SETTINGS_FIELDS};
+
+/// Commonly mistaken field names and the field that was most likely meant
+const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("reset", "anchor"),
+    ("children", "keys"),
+    ("options", "choices"),
+    ("command", "value"),
+    ("cmd", "value"),
+    ("label", "name"),
+    ("execute", "immediate"),
+];
+
+/// A YAML value annotated with the position where it starts
+#[derive(Debug)]
+struct Marked {
+    kind: MarkedKind,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+enum MarkedKind {
+    Scalar(String),
+    Sequence(Vec<Marked>),
+    Mapping(Vec<(Marked, Marked)>),
+    Alias,
+}
+
+impl Marked {
+    fn new(kind: MarkedKind, mark: Marker) -> Self {
+        Marked {
+            kind,
+            line: mark.line(),
+            // Markers count columns from 0
+            column: mark.col() + 1,
+        }
+    }
+
+    /// The span covered by this value, which for scalars is the whole value
+    fn span(&self) -> Span {
+        let length = match &self.kind {
+            MarkedKind::Scalar(value) => value.chars().count(),
+            _ => 1,
+        };
+        Span::new(self.line, self.column, length)
+    }
+
+    fn as_sequence(&self) -> &[Marked] {
+        match &self.kind {
+            MarkedKind::Sequence(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn as_mapping(&self) -> Option<&[(Marked, Marked)]> {
+        match &self.kind {
+            MarkedKind::Mapping(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    fn as_scalar(&self) -> Option<&str> {
+        match &self.kind {
+            MarkedKind::Scalar(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Parses a scalar as a key, returning `None` if it is not a valid key
+    fn as_key(&self) -> Option<Key> {
+        self.as_scalar()?.parse().ok()
+    }
+
+    fn get(&self, field: &str) -> Option<&Marked> {
+        self.get_entry(field).map(|(_, value)| value)
+    }
+
+    /// Returns both the key and the value of a mapping entry
+    fn get_entry(&self, field: &str) -> Option<&(Marked, Marked)> {
+        self.as_mapping()?
+            .iter()
+            .find(|(key, _)| key.as_scalar() == Some(field))
+    }
+}
+
+/// Builds a tree of marked values from parser events
+#[derive(Default)]
+struct TreeBuilder {
+    /// Containers that are being filled, with the pending key for mappings
+    stack: Vec<(Marked, Option<Marked>)>,
+    root: Option<Marked>,
+}
+
+impl TreeBuilder {
+    fn push_value(&mut self, value: Marked) {
+        let Some((container, pending_key)) = self.stack.last_mut() else {
+            self.root = Some(value);
+            return;
+        };
+        match &mut container.kind {
+            MarkedKind::Sequence(items) => items.push(value),
+            MarkedKind::Mapping(entries) => match pending_key.take() {
+                Some(key) => entries.push((key, value)),
+                None => *pending_key = Some(value),
+            },
+            _ => unreachable!("only sequences and mappings are pushed on the stack"),
+        }
+    }
+}
+
+impl MarkedEventReceiver for TreeBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.push_value(Marked::new(MarkedKind::Scalar(value), mark))
+            }
+            Event::Alias(_) => self.push_value(Marked::new(MarkedKind::Alias, mark)),
+            Event::SequenceStart(..) => self
+                .stack
+                .push((Marked::new(MarkedKind::Sequence(Vec::new()), mark), None)),
+            Event::MappingStart(..) => self
+                .stack
+                .push((Marked::new(MarkedKind::Mapping(Vec::new()), mark), None)),
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some((container, _)) = self.stack.pop() {
+                    self.push_value(container);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the structure of a configuration file and returns every problem
+/// found. Problems with the types of values are left to deserialization.
+pub fn validate(contents: &str) -> Vec<Diagnostic> {
+    let root = match parse(contents) {
+        Ok(root) => root,
+        Err(diagnostic) => return vec![*diagnostic],
+    };
+
+    let mut diagnostics = Vec::new();
+    if let Some(root) = &root {
+        validate_config(root, &mut diagnostics);
+    }
+    diagnostics
+}
+
+/// Returns the span of the `key` of the root node with the given key
+pub fn locate_root_key(contents: &str, key: &Key) -> Option<Span> {
+    let root = parse(contents).ok()??;
+    root.get("keys")?
+        .as_sequence()
+        .iter()
+        .filter_map(|node| node.get("key"))
+        .find(|k| k.as_key().as_ref() == Some(key))
+        .map(Marked::span)
+}
+
+fn parse(contents: &str) -> Result<Option<Marked>, Box<Diagnostic>> {
+    let mut builder = TreeBuilder::default();
+    if let Err(e) = Parser::new_from_str(contents).load(&mut builder, false) {
+        let mark = e.marker();
+        return Err(Box::new(Diagnostic::new(
+            e.info(),
+            Span::new(mark.line(), mark.col() + 1, 1),
+        )));
+    }
+    Ok(builder.root)
+}
+
+fn validate_config(root: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    check_fields(root, CONFIG_FIELDS, diagnostics);
+    if let Some(keys) = root.get("keys") {
+        validate_nodes(keys, diagnostics);
+    }
+    if let Some(extensions) = root.get("extend") {
+        for extension in extensions.as_sequence() {
+            check_fields(extension, EXTENSION_FIELDS, diagnostics);
+            if let Some(keys) = extension.get("keys") {
+                validate_nodes(keys, diagnostics);
+            }
+        }
+    }
+    if let Some(settings) = root.get("settings") {
+        check_fields(settings, SETTINGS_FIELDS, diagnostics);
+        if let Some(bindings) = settings.get("bindings") {
+            validate_bindings(bindings, diagnostics);
+        }
+    }
+}
+
+fn validate_bindings(bindings: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    check_fields(bindings, BINDING_FIELDS, diagnostics);
+    for (_, value) in bindings.as_mapping().unwrap_or_default() {
+        let keys = match &value.kind {
+            MarkedKind::Sequence(keys) => keys.as_slice(),
+            _ => std::slice::from_ref(value),
+        };
+        for key in keys {
+            if let Some(Err(message)) = key.as_scalar().map(str::parse::<Key>) {
+                diagnostics.push(invalid_key(message, key.span()));
+            }
+        }
+    }
+}
+
+fn invalid_key(message: String, span: Span) -> Diagnostic {
+    Diagnostic::new(message, span).with_help(
+        "use a single character, or a key name in angle brackets such as `<C-r>`, `<Tab>` or `<F5>`",
+    )
+}
+
+fn validate_nodes(nodes: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    check_unique_keys(nodes, diagnostics);
+    for node in nodes.as_sequence() {
+        check_fields(node, NODE_FIELDS, diagnostics);
+        check_name(node, diagnostics);
+        check_node_shape(node, diagnostics);
+        if let Some(input) = node.get_entry("input") {
+            check_input(input, diagnostics);
+        }
+        if let Some(keys) = node.get("keys") {
+            validate_nodes(keys, diagnostics);
+        }
+        if let Some(choices) = node.get("choices") {
+            for choice in choices.as_sequence() {
+                check_fields(choice, CHOICE_FIELDS, diagnostics);
+            }
+        }
+    }
+}
+
+/// Reports keys that cannot be parsed, and sibling nodes that share a key
+fn check_unique_keys(nodes: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: Vec<(&Marked, Key)> = Vec::new();
+    for key in nodes.as_sequence().iter().filter_map(|n| n.get("key")) {
+        let Some(value) = key.as_scalar() else {
+            continue;
+        };
+        let parsed = match value.parse::<Key>() {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                diagnostics.push(invalid_key(message, key.span()));
+                continue;
+            }
+        };
+        match seen
+            .iter()
+            .find(|(_, k)| *k == parsed)
+            .map(|(first, _)| first)
+        {
+            Some(first) => diagnostics.push(
+                Diagnostic::new(format!("duplicate key `{}`", value), key.span()).with_note(
+                    format!(
+                        "`{}` is first defined at {}:{}",
+                        value, first.line, first.column
+                    ),
+                ),
+            ),
+            None => seen.push((key, parsed)),
+        }
+    }
+}
+
+/// Reports the fields of a node that do not go together, with the same rules
+/// as deserialization
+fn check_node_shape(node: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    if node.as_mapping().is_none() {
+        return;
+    }
+    let scalar = |field| node.get(field).and_then(Marked::as_scalar);
+    let non_empty = |field| node.get(field).is_some_and(|v| !v.as_sequence().is_empty());
+    let input_type = node
+        .get("input")
+        .and_then(|input| input.get("type").or(Some(input)))
+        .and_then(Marked::as_scalar);
+    let shape = NodeShape {
+        value: scalar("value").unwrap_or_default(),
+        keys: non_empty("keys"),
+        choices: non_empty("choices"),
+        choices_cmd: node.get("choices_cmd").is_some(),
+        input: node.get("input").is_some(),
+        secret: input_type == Some("Secret"),
+        default: node.get("default").is_some(),
+        placeholder: node.get("placeholder").is_some(),
+        multi: scalar("multi").is_some_and(|v| matches!(v, "true" | "True" | "TRUE")),
+        r#match: node.get("match").is_some(),
+        timeout: scalar("timeout").and_then(|v| v.parse().ok()),
+        separator: node.get("separator").is_some(),
+    };
+
+    for problem in shape.problems() {
+        let Some((key, value)) = node.get_entry(problem.field) else {
+            continue;
+        };
+        // Placeholders are found in the value rather than in the field name
+        let span = if problem.field == "value" {
+            value.span()
+        } else {
+            key.span()
+        };
+        let mut diagnostic = Diagnostic::new(problem.message, span);
+        if let Some(help) = problem.help {
+            diagnostic = diagnostic.with_help(help);
+        }
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Reports unknown input types, fields that do not apply to the input type,
+/// and patterns that are not valid regular expressions
+fn check_input((field, input): &(Marked, Marked), diagnostics: &mut Vec<Diagnostic>) {
+    let type_names: Vec<&str> = INPUT_TYPES.iter().map(|&(name, _)| name).collect();
+    let input_type = match input.as_mapping() {
+        Some(_) => input.get("type"),
+        None => Some(input),
+    };
+    let Some(input_type) = input_type else {
+        diagnostics.push(
+            Diagnostic::new("input must have a `type`", field.span())
+                .with_help(format!("use one of {}", type_names.join(", "))),
+        );
+        return;
+    };
+    let Some(name) = input_type.as_scalar() else {
+        return;
+    };
+    let Some(&(_, type_fields)) = INPUT_TYPES.iter().find(|&&(n, _)| n == name) else {
+        let mut diagnostic =
+            Diagnostic::new(format!("unknown input type `{}`", name), input_type.span());
+        diagnostic = match suggest_field(name, &type_names) {
+            Some(suggestion) => diagnostic.with_help(format!("did you mean `{}`?", suggestion)),
+            None => diagnostic.with_help(format!("use one of {}", type_names.join(", "))),
+        };
+        diagnostics.push(diagnostic);
+        return;
+    };
+
+    for (key, value) in input.as_mapping().unwrap_or_default() {
+        let Some(key_name) = key.as_scalar() else {
+            continue;
+        };
+        if key_name == "type" || type_fields.contains(&key_name) {
+            if key_name == "pattern" {
+                if let Some(Err(message)) = value.as_scalar().map(compile_pattern) {
+                    diagnostics.push(Diagnostic::new(message, value.span()));
+                }
+            }
+            continue;
+        }
+        let applies_elsewhere = INPUT_TYPES
+            .iter()
+            .any(|(_, fields)| fields.contains(&key_name));
+        let mut diagnostic = if applies_elsewhere {
+            Diagnostic::new(
+                format!("`{}` does not apply to `{}` input", key_name, name),
+                key.span(),
+            )
+        } else {
+            Diagnostic::new(format!("unknown field `{}`", key_name), key.span())
+        };
+        let known: Vec<&str> = ["type"].iter().chain(type_fields).copied().collect();
+        if let Some(suggestion) = suggest_field(key_name, &known) {
+            diagnostic = diagnostic.with_help(format!("did you mean `{}`?", suggestion));
+        }
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Reports nodes whose name, which defaults to the value, is empty
+fn check_name(node: &Marked, diagnostics: &mut Vec<Diagnostic>) {
+    let name = node.get_entry("name").or_else(|| node.get_entry("value"));
+    let is_empty = name.is_none_or(|(_, value)| value.as_scalar() == Some(""));
+    if !is_empty {
+        return;
+    }
+    let span = name
+        .map(|(key, _)| key.span())
+        .or_else(|| node.get("key").map(Marked::span))
+        .unwrap_or_else(|| node.span());
+    diagnostics.push(
+        Diagnostic::new("name must not be empty", span)
+            .with_help("give the node a `name` or a `value`"),
+    );
+}
+
+/// Reports the fields of a mapping that are not in `known`
+fn check_fields(value: &Marked, known: &[&str], diagnostics: &mut Vec<Diagnostic>) {
+    let Some(entries) = value.as_mapping() else {
+        return;
+    };
+    for (key, _) in entries {
+        let Some(field) = key.as_scalar() else {
+            continue;
+        };
+        if known.contains(&field) {
+            continue;
+        }
+        let mut diagnostic = Diagnostic::new(format!("unknown field `{}`", field), key.span());
+        if let Some(suggestion) = suggest_field(field, known) {
+            diagnostic = diagnostic.with_help(format!("did you mean `{}`?", suggestion));
+        }
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Suggests the known field that was most likely meant by `field`
+fn suggest_field<'a>(field: &str, known: &[&'a str]) -> Option<&'a str> {
+    if let Some(&(_, target)) = FIELD_ALIASES.iter().find(|(alias, _)| *alias == field) {
+        if let Some(&known_field) = known.iter().find(|&&k| k == target) {
+            return Some(known_field);
+        }
+    }
+
+    let max_distance = (field.len() / 3).max(1);
+    known
+        .iter()
+        .map(|&k| (k, strsim::damerau_levenshtein(field, k)))
+        .filter(|&(_, distance)| distance <= max_distance)
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(k, _)| k)
+}
+
+/// The fields of a struct that derives `Deserialize`, as serde sees them.
+/// Tests compare them with the hand-written field lists, so that the lists
+/// can't drift from the structs.
+#[cfg(test)]
+pub fn struct_fields<'de, T: serde::Deserialize<'de>>() -> Vec<&'static str> {
+    use serde::de::{self, Visitor};
+
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("only the field names are wanted"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    let mut fields = fields.to_vec();
+    fields.sort_unstable();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_accepts_valid_config() {
+        let yaml = r#"
+include:
+  - shared/*.yml
+keys:
+  - key: g
+    value: git
+    keys:
+      - key: c
+        value: checkout
+        choices:
+          - main
+          - name: Production
+            value: prod
+extend:
+  - path: g
+    keys:
+      - key: s
+        value: status
+hide:
+  - g R
+"#;
+        assert!(validate(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_validate_placeholders_match_node_kind() {
+        let yaml = r#"keys:
+  - key: k
+    value: kubectl --context {input}
+    choices: [prod, staging]
+  - key: e
+    value: echo {input}
+  - key: g
+    value: git {input}
+    keys:
+      - key: s
+        value: status
+  - key: c
+    value: cd {choice}
+    input: Path
+  - key: b
+    value: git switch {choice}
+    choices_cmd: git branch
+  - key: m
+    value: git commit -m {input}
+    input: Text
+"#;
+        let diagnostics = validate(yaml);
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.span.line).collect();
+        assert_eq!(lines, [3, 6, 8, 13]);
+        assert_eq!(
+            diagnostics[3].message,
+            "`{choice}` is never filled on this node"
+        );
+    }
+
+    #[test]
+    fn test_validate_unknown_field_with_alias_suggestion() {
+        let yaml = r#"keys:
+  - key: h
+    value: gh
+    reset: true
+"#;
+        let diagnostics = validate(yaml);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unknown field `reset`");
+        assert_eq!(
+            diagnostics[0].help.as_deref(),
+            Some("did you mean `anchor`?")
+        );
+        assert_eq!(diagnostics[0].span, Span::new(4, 5, 5));
+    }
+
+    #[test]
+    fn test_validate_unknown_field_with_typo_suggestion() {
+        let yaml = r#"keys:
+  - key: l
+    value: ls
+    lop: true
+"#;
+        let diagnostics = validate(yaml);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].help.as_deref(), Some("did you mean `loop`?"));
+    }
+
+    #[test]
+    fn test_validate_reports_all_problems() {
+        let yaml = r#"kyes: []
+keys:
+  - key: g
+    value: git
+    colour: red
+    keys:
+      - key: s
+        value: status
+        fleting: true
+  - key: c
+    name: pick
+    choices:
+      - lable: x
+        value: y
+"#;
+        let diagnostics = validate(yaml);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "unknown field `kyes`",
+                "unknown field `colour`",
+                "unknown field `fleting`",
+                "unknown field `lable`",
+            ]
+        );
+        assert_eq!(diagnostics[0].help.as_deref(), Some("did you mean `keys`?"));
+        assert_eq!(diagnostics[1].help, None);
+        assert_eq!(diagnostics[2].span.line, 9);
+    }
+
+    #[test]
+    fn test_validate_keys_are_compared_after_parsing() {
+        let yaml = r#"keys:
+  - key: <C-r>
+    value: reload
+  - key: <c-R>
+    value: redo
+  - key: <Foo>
+    value: foo
+"#;
+        let diagnostics = validate(yaml);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec!["invalid key `<Foo>`"]);
+
+        let diagnostics =
+            validate("keys:\n  - key: <C-r>\n    value: a\n  - key: <c-r>\n    value: b\n");
+        assert_eq!(diagnostics[0].message, "duplicate key `<c-r>`");
+        assert_eq!(
+            diagnostics[0].notes,
+            vec!["`<c-r>` is first defined at 2:10"]
+        );
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        let yaml = r#"settings:
+  bindigns: {}
+  bindings:
+    close: <C-c>
+    quit: q
+    next: [<C-j>, <Ctrl-n>]
+"#;
+        let diagnostics = validate(yaml);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "unknown field `bindigns`",
+                "unknown field `quit`",
+                "invalid key `<Ctrl-n>`",
+            ]
+        );
+        assert_eq!(diagnostics[2].span.line, 6);
+    }
+
+    #[test]
+    fn test_validate_input_types() {
+        let yaml = r#"keys:
+  - key: a
+    name: port
+    input: { type: Integer, min: 1, pattern: '\d+' }
+  - key: b
+    name: name
+    input: { type: Text, pattern: '[a-' }
+  - key: c
+    name: env
+    input: Enmu
+  - key: d
+    name: size
+    input: { min: 1 }
+"#;
+        let diagnostics = validate(yaml);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages.len(), 4, "{:?}", messages);
+        assert_eq!(messages[0], "`pattern` does not apply to `Integer` input");
+        assert!(messages[1].starts_with("invalid pattern `[a-`"));
+        assert_eq!(diagnostics[1].span.line, 7);
+        assert_eq!(messages[2], "unknown input type `Enmu`");
+        assert_eq!(diagnostics[2].help.as_deref(), Some("did you mean `Enum`?"));
+        assert_eq!(messages[3], "input must have a `type`");
+    }
+
+    #[test]
+    fn test_validate_syntax_error() {
+        let diagnostics = validate("keys: value: other\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span.line, 1);
+    }
+}